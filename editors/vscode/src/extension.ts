@@ -9,11 +9,51 @@ import {
   ServerOptions,
 } from "vscode-languageclient/node";
 
-type PageName = "status" | "annotate";
-const allPages: PageName[] = ["status", "annotate"];
+type PageName = "status" | "annotate" | "oplog" | "bookmarks" | "conflicts" | "rebase";
+const allPages: PageName[] = ["status", "annotate", "oplog", "bookmarks", "conflicts", "rebase"];
 
 let client: LanguageClient;
 
+/**
+ * Serves the `jjmagit:` pages, which the language server renders on request. Saving a page
+ * writes nothing, the server applies the edits when it is notified of the save.
+ */
+class PageFileSystemProvider implements vscode.FileSystemProvider {
+  // Open pages are updated by the server with `workspace/applyEdit`.
+  readonly onDidChangeFile = new vscode.EventEmitter<vscode.FileChangeEvent[]>().event;
+
+  watch(): vscode.Disposable {
+    return new vscode.Disposable(() => {});
+  }
+
+  stat(): vscode.FileStat {
+    return { type: vscode.FileType.File, ctime: 0, mtime: Date.now(), size: 0 };
+  }
+
+  async readFile(uri: vscode.Uri): Promise<Uint8Array> {
+    const text = await client.sendRequest<string>("jjmagit/page", { uri: uri.toString() });
+    return new TextEncoder().encode(text);
+  }
+
+  writeFile() {}
+
+  readDirectory(): [string, vscode.FileType][] {
+    throw vscode.FileSystemError.NoPermissions();
+  }
+
+  createDirectory() {
+    throw vscode.FileSystemError.NoPermissions();
+  }
+
+  delete() {
+    throw vscode.FileSystemError.NoPermissions();
+  }
+
+  rename() {
+    throw vscode.FileSystemError.NoPermissions();
+  }
+}
+
 let outputChannel: vscode.OutputChannel;
 export async function activate(context: ExtensionContext) {
   outputChannel = window.createOutputChannel("jjmagit language server");
@@ -36,7 +76,9 @@ export async function activate(context: ExtensionContext) {
     debug: run,
   };
   let clientOptions: LanguageClientOptions = {
-    documentSelector: [{ scheme: "file", language: "jjmagit" }],
+    documentSelector: [{ scheme: "jjmagit", language: "jjmagit" }],
+    // The server prompts for text, e.g. a revset to rebase onto, with `jjmagit/showInputBox`.
+    initializationOptions: { showInputBox: true },
     outputChannel,
     traceOutputChannel,
   };
@@ -47,6 +89,15 @@ export async function activate(context: ExtensionContext) {
     serverOptions,
     clientOptions,
   );
+  const pages = new PageFileSystemProvider();
+  context.subscriptions.push(
+    vscode.workspace.registerFileSystemProvider("jjmagit", pages, { isCaseSensitive: true }),
+  );
+
+  client.onRequest("jjmagit/showInputBox", async ({ prompt }: { prompt: string }) => {
+    return (await vscode.window.showInputBox({ prompt })) ?? null;
+  });
+
   client.start();
 
   context.subscriptions.push(vscode.workspace.onDidOpenTextDocument(onDidOpenTextDocument));
@@ -59,6 +110,10 @@ export async function activate(context: ExtensionContext) {
 
   registerPage("status", () => openPage("status"));
   registerPage("annotate", () => openPage("annotate", true));
+  registerPage("oplog", () => openPage("oplog"));
+  registerPage("bookmarks", () => openPage("bookmarks"));
+  registerPage("conflicts", () => openPage("conflicts"));
+  registerPage("rebase", () => openPage("rebase"));
 }
 
 export function deactivate(): Thenable<void> | undefined {
@@ -116,7 +171,7 @@ async function openPage(page: PageName, includePath: boolean = false) {
     return;
   }
 
-  let document = await vscode.workspace.openTextDocument(vscode.Uri.file(response));
+  let document = await vscode.workspace.openTextDocument(vscode.Uri.parse(response));
   let editor = await vscode.window.showTextDocument(document);
 
   await foldAll();
@@ -8,8 +8,10 @@ use std::io::Write;
 fn main() -> Result<()> {
     let repo = Repo::detect_cwd()?.unwrap();
 
-    let mut out = PageWriter::default();
-    out.debug = true;
+    let mut out = PageWriter {
+        debug: true,
+        ..Default::default()
+    };
 
     let status = pages::Status;
     status.render(&mut out, &repo, &[])?;
@@ -1,30 +1,663 @@
-use crate::jj::Repo;
+use crate::jj::{DiffHunk, Direction, RebaseAction, RebaseMode, Repo};
 use crate::page_writer::PageWriter;
 use crate::pages::{self, Page};
 use anyhow::{Result, anyhow};
-use std::path::{Path, PathBuf};
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use jj_cli::revset_util::parse_bookmark_name;
+use jj_lib::commit::Commit;
+use jj_lib::matchers::{DifferenceMatcher, EverythingMatcher, FilesMatcher, Matcher};
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::MergedTree;
+use jj_lib::ref_name::RefNameBuf;
+use jj_lib::repo_path::RepoPathBuf;
+use tower_lsp::lsp_types::Url;
 
 pub const OPEN: &str = "open";
+pub const ABANDON: &str = "abandon";
+pub const NEW: &str = "new";
+pub const EDIT: &str = "edit";
+pub const DUPLICATE: &str = "duplicate";
+pub const NEXT: &str = "next";
+pub const PREV: &str = "prev";
+pub const SQUASH: &str = "squash";
+pub const RESTORE: &str = "restore";
+pub const SQUASH_HUNK: &str = "squash-hunk";
+pub const SPLIT: &str = "split";
+pub const SPLIT_HUNK: &str = "split-hunk";
+pub const RESTORE_HUNK: &str = "restore-hunk";
+pub const CREATE_BOOKMARK: &str = "create-bookmark";
+pub const MOVE_BOOKMARK: &str = "move-bookmark";
+pub const DELETE_BOOKMARK: &str = "delete-bookmark";
+pub const FORGET_BOOKMARK: &str = "forget-bookmark";
+pub const TRACK_BOOKMARK: &str = "track-bookmark";
+pub const UNTRACK_BOOKMARK: &str = "untrack-bookmark";
+pub const UNDO_OPERATION: &str = "undo-operation";
+pub const RESTORE_OPERATION: &str = "restore-operation";
+pub const RESOLVE_CONFLICT: &str = "resolve-conflict";
+pub const RESTORE_FILE: &str = "restore-file";
+pub const REBASE: &str = "rebase";
 pub const TODO: &str = "todo";
 
-pub async fn open_page(workspace: &Path, page: &dyn Page, arguments: &[&str]) -> Result<PathBuf> {
-    let (page_path, page) = {
-        let repo = Repo::detect(workspace)?.ok_or_else(|| anyhow!("no jj root found"))?;
+/// Errors in individual lines of the text of an editable region, as `(line index, message)`.
+#[derive(Debug)]
+pub struct LineErrors(pub Vec<(usize, String)>);
 
-        let page_path = pages::path::get_path(repo.workspace_dir(), page, arguments);
+impl std::fmt::Display for LineErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.as_slice() {
+            [(line, message)] => write!(f, "line {}: {message}", line + 1),
+            errors => write!(f, "{} lines have errors", errors.len()),
+        }
+    }
+}
 
-        let mut out = PageWriter::default();
-        page.render(&mut out, &repo, arguments)?;
+impl std::error::Error for LineErrors {}
+
+/// The URI of `page` in the workspace of `repo`. Its contents are rendered when the client
+/// requests them.
+pub fn open_page(repo: &Repo, page: &dyn Page, arguments: &[&str]) -> Url {
+    pages::path::get_uri(repo.workspace_dir(), page, arguments)
+}
+
+pub fn abandon(repo: &mut Repo, change_id: &str) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+    repo.abandon(&commit)?;
+
+    Ok(())
+}
+
+pub fn new(repo: &mut Repo, change_id: &str) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+    repo.new_commit(&commit)?;
+
+    Ok(())
+}
+
+pub fn edit(repo: &mut Repo, change_id: &str) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+    repo.edit(&commit)?;
+
+    Ok(())
+}
+
+pub fn duplicate(repo: &mut Repo, change_id: &str) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+    repo.duplicate(&commit)?;
+
+    Ok(())
+}
+
+/// Moves the working copy like `jj next` or `jj prev`, depending on `direction`.
+pub fn move_working_copy(repo: &mut Repo, direction: &str) -> Result<()> {
+    let direction = match direction {
+        NEXT => Direction::Next,
+        PREV => Direction::Prev,
+        _ => return Err(anyhow!("unknown direction: {direction}")),
+    };
+
+    repo.move_working_copy(direction)?;
+
+    Ok(())
+}
+
+/// Creates a bookmark at `change_id`, named like `jj git push --change` would name it.
+pub fn create_bookmark(repo: &mut Repo, change_id: &str) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+    let name = repo.push_bookmark_name(&commit)?;
+    repo.create_bookmarks(&[(name.into(), commit)])?;
+
+    Ok(())
+}
+
+/// Creates a bookmark for every line of `text` of the form `<name> [<revision>]`, where the
+/// revision defaults to `@`. Nothing is created unless every line is valid.
+pub fn create_bookmarks(repo: &mut Repo, text: &str) -> Result<()> {
+    let mut bookmarks: Vec<(RefNameBuf, Commit)> = Vec::new();
+    let mut errors = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("JJ:") {
+            continue;
+        }
+        let (name, revision) = match line.split_once(char::is_whitespace) {
+            Some((name, revision)) => (name, revision.trim()),
+            None => (line, "@"),
+        };
+
+        let bookmark = parse_bookmark_name(name)
+            .map_err(|e| e.to_string())
+            .and_then(|name| {
+                if repo.inner().view().get_local_bookmark(&name).is_present() {
+                    Err(format!("Bookmark {} already exists", name.as_symbol()))
+                } else if bookmarks.iter().any(|(listed, _)| *listed == name) {
+                    Err(format!("Bookmark {} is listed twice", name.as_symbol()))
+                } else {
+                    let commit = repo.revset_single(revision).map_err(|e| e.to_string())?;
+                    Ok((name, commit))
+                }
+            });
+        match bookmark {
+            Ok(bookmark) => bookmarks.push(bookmark),
+            Err(message) => errors.push((index, message)),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(LineErrors(errors).into());
+    }
+    if bookmarks.is_empty() {
+        return Ok(());
+    }
+
+    repo.create_bookmarks(&bookmarks)
+}
+
+pub fn move_bookmark(repo: &mut Repo, name: &str, revision: &str) -> Result<()> {
+    let commit = repo.revset_single(revision)?;
+    repo.move_bookmark(name, &commit)?;
+
+    Ok(())
+}
+
+pub fn delete_bookmark(repo: &mut Repo, name: &str) -> Result<()> {
+    repo.delete_bookmark(name)?;
+
+    Ok(())
+}
+
+pub fn forget_bookmark(repo: &mut Repo, name: &str) -> Result<()> {
+    repo.forget_bookmark(name)?;
+
+    Ok(())
+}
+
+pub fn track_bookmark(repo: &mut Repo, name: &str, remote: &str) -> Result<()> {
+    repo.track_bookmark(name, remote)?;
+
+    Ok(())
+}
+
+pub fn untrack_bookmark(repo: &mut Repo, name: &str, remote: &str) -> Result<()> {
+    repo.untrack_bookmark(name, remote)?;
+
+    Ok(())
+}
+
+/// Reverts the changes made by the operation `op_id`.
+pub fn undo_operation(repo: &mut Repo, op_id: &str) -> Result<()> {
+    let operation = repo.operation(op_id)?;
+    repo.revert_operation(&operation)?;
+
+    Ok(())
+}
+
+/// Restores the repo to its state after the operation `op_id`.
+pub fn restore_operation(repo: &mut Repo, op_id: &str) -> Result<()> {
+    let operation = repo.operation(op_id)?;
+    repo.restore_operation(&operation)?;
+
+    Ok(())
+}
+
+/// Sets the description of `change_id` to `text`, dropping `JJ:` lines and surrounding blank
+/// lines like `jj describe` does for descriptions written in an editor.
+pub fn describe(repo: &mut Repo, change_id: &str, text: &str) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+
+    let description: String = text
+        .lines()
+        .filter(|line| !line.starts_with("JJ:"))
+        .map(|line| format!("{}\n", line.trim_end()))
+        .collect();
+    let description = description.trim_matches('\n');
+    let description = if description.is_empty() {
+        String::new()
+    } else {
+        format!("{description}\n")
+    };
+    repo.describe(&commit, description)?;
+
+    Ok(())
+}
+
+/// Rebases `change_id` relative to `destination`, where `mode` is one of `revision`, `branch`,
+/// `after` or `before`.
+pub fn rebase_commit(
+    repo: &mut Repo,
+    change_id: &str,
+    mode: &str,
+    destination: &str,
+) -> Result<()> {
+    let mode = match mode {
+        "revision" => RebaseMode::Revision,
+        "branch" => RebaseMode::Branch,
+        "after" => RebaseMode::InsertAfter,
+        "before" => RebaseMode::InsertBefore,
+        _ => return Err(anyhow!("unknown rebase mode: {mode}")),
+    };
+
+    let commit = repo.revset_single(change_id)?;
+    let destination = repo.revset_single(destination)?;
+    repo.rebase(&commit, mode, &destination)?;
+
+    Ok(())
+}
+
+/// Applies the rebase plan `text` to the linear chain `change_ids`, oldest first. Commits without
+/// a line in the plan are dropped.
+pub fn rebase(repo: &mut Repo, change_ids: &[String], text: &str) -> Result<()> {
+    if change_ids.is_empty() {
+        return Ok(());
+    }
+    let commits = repo.linear_commits(&change_ids.join(" | "))?;
+
+    let mut plan: Vec<(RebaseAction, Commit)> = Vec::new();
+    let mut errors = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("JJ:") {
+            continue;
+        }
+        let step = parse_rebase_step(line, &commits).and_then(|(action, commit)| {
+            if plan.iter().any(|(_, planned)| planned.id() == commit.id()) {
+                Err(format!(
+                    "{} is already part of the plan",
+                    commit.change_id()
+                ))
+            } else if action == RebaseAction::Squash
+                && plan.iter().all(|(action, _)| *action == RebaseAction::Drop)
+            {
+                Err("The first commit can't be squashed".to_owned())
+            } else {
+                Ok((action, commit))
+            }
+        });
+        match step {
+            Ok(step) => plan.push(step),
+            Err(message) => errors.push((index, message)),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(LineErrors(errors).into());
+    }
+
+    for commit in &commits {
+        if !plan.iter().any(|(_, planned)| planned.id() == commit.id()) {
+            plan.push((RebaseAction::Drop, commit.clone()));
+        }
+    }
+    let onto = commits[0].parent_ids().to_vec();
+    repo.rebase_plan(&onto, &plan)?;
+
+    Ok(())
+}
 
-        let page = out.finish().text;
-        (page_path, page)
+/// Parses a line of the form `<action> <change> [<subject>]`, where `<change>` is a prefix of the
+/// change id of one of `commits`.
+fn parse_rebase_step(line: &str, commits: &[Commit]) -> Result<(RebaseAction, Commit), String> {
+    let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim_start();
+    let (change_id, subject) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let subject = subject.trim();
+
+    let action = match word {
+        "pick" | "p" => RebaseAction::Pick,
+        "reword" | "r" if subject.is_empty() => {
+            return Err("reword needs the new subject after the change id".to_owned());
+        }
+        "reword" | "r" => RebaseAction::Reword(subject.to_owned()),
+        "squash" | "s" => RebaseAction::Squash,
+        "drop" | "d" => RebaseAction::Drop,
+        _ => {
+            return Err(format!(
+                "Unknown action '{word}', expected pick, reword, squash or drop"
+            ));
+        }
+    };
+
+    if change_id.is_empty() {
+        return Err(format!("Expected a change id after '{word}'"));
+    }
+    let mut matches = commits
+        .iter()
+        .filter(|commit| commit.change_id().to_string().starts_with(change_id));
+    match (matches.next(), matches.next()) {
+        (Some(commit), None) => Ok((action, commit.clone())),
+        (None, _) => Err(format!(
+            "{change_id} is not one of the commits being rebased"
+        )),
+        (Some(_), Some(_)) => Err(format!("{change_id} is ambiguous")),
+    }
+}
+
+/// Moves the changes to `paths` (or all changes, if empty) from `source` into `destination`.
+pub fn squash(repo: &mut Repo, source: &str, destination: &str, paths: &[&str]) -> Result<()> {
+    let source = repo.revset_single(source)?;
+    let destination = repo.revset_single(destination)?;
+
+    let matcher: Box<dyn Matcher> = if paths.is_empty() {
+        Box::new(EverythingMatcher)
+    } else {
+        let paths = paths
+            .iter()
+            .map(|path| RepoPathBuf::from_internal_string(*path))
+            .collect::<Result<Vec<_>, _>>()?;
+        Box::new(FilesMatcher::new(paths))
+    };
+    let selected_tree = repo.select_paths(&source, matcher.as_ref())?;
+    repo.squash(&source, &destination, selected_tree)?;
+
+    Ok(())
+}
+
+/// Moves the changes to `paths` in `change_id` into a new commit `before` or `after` it, like
+/// `jj split <paths>`.
+pub fn split(repo: &mut Repo, change_id: &str, position: &str, paths: &[&str]) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+
+    let paths = paths
+        .iter()
+        .map(|path| RepoPathBuf::from_internal_string(*path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let matcher = FilesMatcher::new(paths);
+    // The first commit of the split gets the selected changes, so splitting the paths off after
+    // the commit selects all the other paths instead.
+    let selected_tree = match position {
+        "before" => repo.select_paths(&commit, &matcher)?,
+        "after" => {
+            repo.select_paths(&commit, &DifferenceMatcher::new(EverythingMatcher, matcher))?
+        }
+        _ => return Err(anyhow!("unknown split position: {position}")),
     };
+    repo.split(&commit, selected_tree)?;
+
+    Ok(())
+}
+
+/// Discards the changes to `paths` in `change_id`, restoring them from its parents like
+/// `jj restore <paths>`.
+pub fn restore(repo: &mut Repo, change_id: &str, paths: &[&str]) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+
+    let paths = paths
+        .iter()
+        .map(|path| RepoPathBuf::from_internal_string(*path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let selected_tree = repo.select_paths(&commit, &FilesMatcher::new(paths))?;
+    repo.restore(&commit, selected_tree)?;
+
+    Ok(())
+}
+
+pub fn squash_hunk(
+    repo: &mut Repo,
+    source: &str,
+    destination: &str,
+    path: &str,
+    hunk: &str,
+    checksum: &str,
+) -> Result<()> {
+    let source = repo.revset_single(source)?;
+    let destination = repo.revset_single(destination)?;
+
+    let selected_tree = select_hunk(repo, &source, path, hunk, checksum)?;
+    repo.squash(&source, &destination, selected_tree)?;
+
+    Ok(())
+}
+
+pub fn split_hunk(
+    repo: &mut Repo,
+    change_id: &str,
+    path: &str,
+    hunk: &str,
+    checksum: &str,
+) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+
+    let selected_tree = select_hunk(repo, &commit, path, hunk, checksum)?;
+    repo.split(&commit, selected_tree)?;
+
+    Ok(())
+}
+
+pub fn restore_hunk(
+    repo: &mut Repo,
+    change_id: &str,
+    path: &str,
+    hunk: &str,
+    checksum: &str,
+) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+
+    let selected_tree = select_hunk(repo, &commit, path, hunk, checksum)?;
+    repo.restore(&commit, selected_tree)?;
+
+    Ok(())
+}
+
+fn select_hunk(
+    repo: &Repo,
+    commit: &Commit,
+    path: &str,
+    hunk: &str,
+    checksum: &str,
+) -> Result<MergedTree> {
+    let path = RepoPathBuf::from_internal_string(path)?;
+    let hunk = DiffHunk::parse(hunk).ok_or_else(|| anyhow!("invalid hunk: {hunk}"))?;
+    repo.select_hunk(commit, &path, &hunk, checksum)
+}
+
+/// Resolves the conflict at `path` in `change_id` by taking the contents of its `side`th side.
+pub fn resolve_conflict(repo: &mut Repo, change_id: &str, path: &str, side: &str) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+    let path = RepoPathBuf::from_internal_string(path)?;
+    let side: usize = side.parse()?;
+
+    let conflict = commit.tree().path_value(&path)?.simplify();
+    let value = conflict.get_add(side).ok_or_else(|| {
+        anyhow!(
+            "Conflict in {} has no side {}",
+            path.as_internal_file_string(),
+            side + 1
+        )
+    })?;
+    repo.resolve(&commit, &path, Merge::resolved(value.clone()))?;
+
+    Ok(())
+}
+
+/// Replaces the contents of `path` in `change_id` with its contents in `source`.
+pub fn restore_file(repo: &mut Repo, change_id: &str, source: &str, path: &str) -> Result<()> {
+    let commit = repo.revset_single(change_id)?;
+    let source = repo.revset_single(source)?;
+    let path = RepoPathBuf::from_internal_string(path)?;
+
+    let value = source.tree().path_value(&path)?;
+    repo.resolve(&commit, &path, value)?;
+
+    Ok(())
+}
+
+/// The revisions offered when picking a revision, e.g. to rebase onto. Others can be entered as
+/// a revset.
+const NEARBY_REVISIONS: &str = "@ | @- | trunk()";
+
+/// Lists the revisions of [`NEARBY_REVISIONS`] other than `exclude`, as `(title, change_id)`
+/// pairs. With `mutable_only`, immutable revisions are left out.
+pub fn revision_choices(
+    repo: &Repo,
+    exclude: &str,
+    mutable_only: bool,
+) -> Result<Vec<(String, String)>> {
+    let exclude = repo.revset_single(exclude)?;
+    let template = repo.settings_commit_template("templates.commit_summary")?;
+
+    let mut choices = Vec::new();
+    for commit in repo.revset_commits(NEARBY_REVISIONS)? {
+        if commit.id() == exclude.id() || (mutable_only && repo.is_immutable(&commit)?) {
+            continue;
+        }
+
+        let mut out = PageWriter::default();
+        template.format(&commit, &mut out.plaintext())?;
+        choices.push((out.buf, commit.change_id().to_string()));
+    }
+
+    Ok(choices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_lib::config::StackedConfig;
+    use jj_lib::settings::UserSettings;
+    use jj_lib::workspace::Workspace;
+
+    /// A new repository in a temporary directory with the chain `a`, `b`, `c` on top of the root
+    /// commit and the working-copy commit `@` on top of `c`.
+    fn chain_repo(name: &str) -> (Repo, Vec<Commit>) {
+        let dir = std::env::temp_dir().join(format!("jjmagit-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = UserSettings::from_config(StackedConfig::with_defaults()).unwrap();
+        Workspace::init_simple(&settings, &dir).unwrap();
+
+        let mut repo = Repo::detect(&dir).unwrap().unwrap();
+        let mut commits = Vec::new();
+        for subject in ["a", "b", "c", "wc"] {
+            let commit = repo.current_commit().unwrap();
+            repo.describe(&commit, format!("{subject}\n")).unwrap();
+            let commit = repo.current_commit().unwrap();
+            repo.new_commit(&commit).unwrap();
+            commits.push(commit);
+        }
+        let wc = commits.pop().unwrap();
+        repo.edit(&wc).unwrap();
+        (repo, commits)
+    }
+
+    fn change_ids(commits: &[Commit]) -> Vec<String> {
+        commits
+            .iter()
+            .map(|commit| commit.change_id().to_string())
+            .collect()
+    }
+
+    fn subject(repo: &Repo, revset: &str) -> String {
+        let commit = repo.revset_single(revset).unwrap();
+        commit.description().trim_end().to_owned()
+    }
+
+    #[test]
+    fn parse_rebase_step_actions() {
+        let (_repo, commits) = chain_repo("parse-rebase-step");
+        let [a, b, _] = change_ids(&commits).try_into().unwrap();
+
+        let (action, commit) = parse_rebase_step(&format!("pick {a} a"), &commits).unwrap();
+        assert_eq!(action, RebaseAction::Pick);
+        assert_eq!(commit.id(), commits[0].id());
+
+        let (action, commit) =
+            parse_rebase_step(&format!("r  {:.8}  new subject ", b), &commits).unwrap();
+        assert_eq!(action, RebaseAction::Reword("new subject".to_owned()));
+        assert_eq!(commit.id(), commits[1].id());
+
+        let (action, _) = parse_rebase_step(&format!("s {b}"), &commits).unwrap();
+        assert_eq!(action, RebaseAction::Squash);
+        let (action, _) = parse_rebase_step(&format!("drop {a}"), &commits).unwrap();
+        assert_eq!(action, RebaseAction::Drop);
+    }
+
+    #[test]
+    fn parse_rebase_step_errors() {
+        let (_repo, commits) = chain_repo("parse-rebase-step-errors");
+        let a = commits[0].change_id().to_string();
+
+        let error = |line: &str| parse_rebase_step(line, &commits).unwrap_err();
+        assert_eq!(
+            error(&format!("fixup {a}")),
+            "Unknown action 'fixup', expected pick, reword, squash or drop"
+        );
+        assert_eq!(
+            error(&format!("reword {a}")),
+            "reword needs the new subject after the change id"
+        );
+        assert_eq!(error("pick"), "Expected a change id after 'pick'");
+        assert_eq!(
+            error("pick zzzzzzzz"),
+            "zzzzzzzz is not one of the commits being rebased"
+        );
+    }
+
+    #[test]
+    fn rebase_reports_line_errors() {
+        let (mut repo, commits) = chain_repo("rebase-line-errors");
+        let [a, b, c] = change_ids(&commits).try_into().unwrap();
+
+        let text = format!("JJ: comment\nsquash {a}\n\npick {b}\npick {b}\npick {c}\n");
+        let error = rebase(&mut repo, &[a.clone(), b.clone(), c.clone()], &text).unwrap_err();
+        let errors = error.downcast_ref::<LineErrors>().unwrap();
+        assert_eq!(
+            errors.0,
+            [
+                (1, "The first commit can't be squashed".to_owned()),
+                (
+                    4,
+                    format!("{} is already part of the plan", commits[1].change_id())
+                ),
+            ]
+        );
+        assert_eq!(error.to_string(), "2 lines have errors");
+
+        let text = format!("pick {a}\nfixup {b}\n");
+        let error = rebase(&mut repo, &[a, b, c], &text).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2: Unknown action 'fixup', expected pick, reword, squash or drop"
+        );
+    }
+
+    #[test]
+    fn rebase_keeps_descendants_on_the_head() {
+        let (mut repo, commits) = chain_repo("rebase-descendants");
+        let [a, b, c] = change_ids(&commits).try_into().unwrap();
+
+        let text = format!("pick {c}\npick {a}\nreword {b} b2\n");
+        rebase(&mut repo, &[a.clone(), b.clone(), c.clone()], &text).unwrap();
+        assert_eq!(subject(&repo, &format!("{c}-")), "");
+        assert_eq!(subject(&repo, &format!("{a}-")), "c");
+        assert_eq!(subject(&repo, &format!("{b}-")), "a");
+        assert_eq!(subject(&repo, &b), "b2");
+        assert_eq!(subject(&repo, "@-"), "b2");
+
+        let text = format!("pick {a}\ndrop {b}\n");
+        rebase(&mut repo, &[c, a, b], &text).unwrap();
+        assert_eq!(subject(&repo, "@-"), "a");
+        assert_eq!(subject(&repo, "@--"), "");
+    }
+
+    #[test]
+    fn bookmarks_check_the_latest_operation() {
+        let (mut repo, commits) = chain_repo("bookmarks");
+        let mut other = Repo::detect(repo.workspace_dir()).unwrap().unwrap();
+        other
+            .create_bookmarks(&[("main".into(), commits[1].clone())])
+            .unwrap();
 
-    tokio::fs::create_dir_all(&page_path.parent().unwrap()).await?;
-    let mut file = File::create(&page_path).await?;
-    file.write_all(page.as_bytes()).await?;
+        let error = repo
+            .create_bookmarks(&[("main".into(), commits[2].clone())])
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Bookmark main already exists");
+        let error = repo.move_bookmark("main", &commits[0]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Refusing to move bookmark main backwards or sideways"
+        );
+        repo.move_bookmark("main", &commits[2]).unwrap();
 
-    Ok(page_path)
+        other.delete_bookmark("main").unwrap();
+        let error = repo.delete_bookmark("main").unwrap_err();
+        assert_eq!(error.to_string(), "No such bookmark: main");
+        let error = repo.move_bookmark("main", &commits[2]).unwrap_err();
+        assert_eq!(error.to_string(), "No such bookmark: main");
+    }
 }
@@ -1,40 +1,78 @@
 use chrono::TimeZone as _;
 use futures_executor::block_on_stream;
 use jj_cli::cli_util::default_ignored_remote_name;
+use jj_cli::commit_ref_list::{self, RefFilterPredicates, RefListItem};
 use jj_cli::commit_templater::{
-    AnnotationLine, CommitTemplateLanguage, CommitTemplateLanguageExtension,
+    AnnotationLine, CommitRef, CommitTemplateLanguage, CommitTemplateLanguageExtension,
 };
 use jj_cli::config::{ConfigEnv, config_from_environment, default_config_layers};
+use jj_cli::description_util::try_combine_messages;
 use jj_cli::diff_util::{self, UnifiedDiffOptions, show_diff_summary};
-use jj_cli::formatter::Formatter;
+use jj_cli::formatter::{Formatter, PlainTextFormatter};
+use jj_cli::operation_templater::{OperationTemplateLanguage, OperationTemplateLanguageExtension};
 use jj_cli::revset_util::{self, RevsetExpressionEvaluator};
 use jj_cli::template_builder::{self, TemplateLanguage};
 use jj_cli::template_parser::{TemplateAliasesMap, TemplateDiagnostics};
 use jj_cli::templater::{TemplateRenderer, WrapTemplateProperty};
 use jj_lib::annotate::{FileAnnotation, FileAnnotator};
+use jj_lib::backend::{CommitId, TreeValue};
 use jj_lib::commit::Commit;
 use jj_lib::config::{ConfigGetError, ConfigGetResultExt, ConfigNamePathBuf, StackedConfig};
-use jj_lib::conflicts::{ConflictMarkerStyle, MaterializedTreeDiffEntry, materialized_diff_stream};
+use jj_lib::conflicts::{
+    ConflictMarkerStyle, MaterializedTreeDiffEntry, MaterializedTreeValue, materialize_tree_value,
+    materialized_diff_stream,
+};
+use jj_lib::content_hash::blake2b_hash;
 use jj_lib::copies::CopyRecords;
 use jj_lib::diff_presentation::LineCompareMode;
+use jj_lib::evolution::{self, CommitEvolutionEntry};
+use jj_lib::file_util::expand_home_path;
+use jj_lib::fileset::{self, FilesetDiagnostics};
+use jj_lib::git;
+use jj_lib::gitignore::GitIgnoreFile;
+use jj_lib::graph::{GraphNode, TopoGroupedGraphIterator};
+use jj_lib::hex_util;
 use jj_lib::id_prefix::IdPrefixContext;
-use jj_lib::matchers::{EverythingMatcher, Matcher};
-use jj_lib::merge::Diff;
+use jj_lib::matchers::{EverythingMatcher, Matcher, NothingMatcher};
+use jj_lib::merge::{Diff, Merge, MergedTreeValue};
 use jj_lib::merged_tree::MergedTree;
-use jj_lib::ref_name::RemoteName;
-use jj_lib::repo::{ReadonlyRepo, Repo as _, StoreFactories};
-use jj_lib::repo_path::RepoPathUiConverter;
+use jj_lib::merged_tree_builder::MergedTreeBuilder;
+use jj_lib::op_store::{self, OperationId, RefTarget};
+use jj_lib::op_walk;
+use jj_lib::operation::Operation;
+use jj_lib::ref_name::{RefName, RefNameBuf, RemoteName, RemoteNameBuf};
+use jj_lib::refs;
+use jj_lib::repo::{MutableRepo, ReadonlyRepo, Repo as _, StoreFactories};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf, RepoPathUiConverter};
 use jj_lib::revset::{
     self, RevsetAliasesMap, RevsetDiagnostics, RevsetExpression, RevsetExtensions,
     RevsetIteratorExt, RevsetParseContext, RevsetWorkspaceContext, UserRevsetExpression,
 };
-use jj_lib::settings::UserSettings;
+use jj_lib::rewrite::{
+    CommitWithSelection, MoveCommitsLocation, MoveCommitsTarget, RebaseOptions, RewriteRefsOptions,
+    duplicate_commits_onto_parents, move_commits, rebase_commit, rebase_to_dest_parent,
+    restore_tree, squash_commits,
+};
+use jj_lib::settings::{HumanByteSize, UserSettings};
+use jj_lib::str_util::StringMatcher;
+use jj_lib::transaction::Transaction;
+use jj_lib::view::View;
+use jj_lib::working_copy::{SnapshotOptions, WorkingCopyFreshness};
 use jj_lib::workspace::Workspace;
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::slice;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
 
-use anyhow::{Result, anyhow, ensure};
+use anyhow::{Context, Result, anyhow, ensure};
+
+/// How many generations of ancestors an annotation searches between checks whether the render
+/// was cancelled.
+const ANNOTATION_GENERATIONS: u64 = 100;
 
 pub struct Repo {
     workspace: Workspace,
@@ -42,6 +80,8 @@ pub struct Repo {
     settings: UserSettings,
 
     id_prefix_context: IdPrefixContext,
+    /// The operation that the disambiguation index of `id_prefix_context` is loaded for.
+    id_prefix_op_id: OperationId,
     path_converter: RepoPathUiConverter,
 
     revset_aliases_map: RevsetAliasesMap,
@@ -49,8 +89,74 @@ pub struct Repo {
     template_aliases_map: TemplateAliasesMap,
     default_ignored_remote: Option<&'static RemoteName>,
     revsets_use_glob_by_default: bool,
+    conflict_marker_style: ConflictMarkerStyle,
 
     immutable_heads_expression: Arc<UserRevsetExpression>,
+
+    /// The user config files and directories, and their modification times when the settings
+    /// were loaded from them.
+    config_paths: Vec<PathBuf>,
+    config_mtimes: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+/// A hunk of a file diff, as zero-based line ranges into the old and new file contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    pub before: Range<usize>,
+    pub after: Range<usize>,
+}
+
+/// What an operation changed compared to its parent operation.
+pub struct OperationChanges {
+    /// Commits that became visible.
+    pub added: Vec<Commit>,
+    /// Commits that were hidden, e.g. because they were rewritten or abandoned.
+    pub removed: Vec<Commit>,
+    /// Local bookmarks with their targets before and after the operation.
+    pub bookmarks: Vec<(RefNameBuf, RefTarget, RefTarget)>,
+}
+
+struct FileContent {
+    value: Option<TreeValue>,
+    content: Vec<u8>,
+}
+
+/// What to do with a commit of a rebase plan, like the commands of `git rebase --interactive`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebaseAction {
+    Pick,
+    /// Keeps the commit, replacing the first line of its description.
+    Reword(String),
+    /// Melds the commit into the commit before it in the plan.
+    Squash,
+    Drop,
+}
+
+/// How `jj rebase` moves a commit to its destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebaseMode {
+    /// Moves only the commit onto the destination, like `jj rebase -r <commit> -o <destination>`.
+    Revision,
+    /// Moves the commits of the commit's branch that aren't ancestors of the destination onto it,
+    /// like `jj rebase -b <commit> -o <destination>`.
+    Branch,
+    /// Inserts the commit between the destination and its children, like `jj rebase -A`.
+    InsertAfter,
+    /// Inserts the commit between the destination and its parents, like `jj rebase -B`.
+    InsertBefore,
+}
+
+/// Which way `jj next` and `jj prev` move the working copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Next,
+    Prev,
+}
+
+/// A transaction on a [`Repo`], finished with [`RepoTransaction::finish`].
+pub struct RepoTransaction<'a> {
+    base: &'a mut Repo,
+    tx: Transaction,
 }
 
 pub struct DiffState<'a> {
@@ -59,6 +165,15 @@ pub struct DiffState<'a> {
     tree: Diff<MergedTree>,
 }
 
+impl std::fmt::Debug for Repo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Repo")
+            .field("workspace", &self.workspace_dir())
+            .field("operation", self.repo.op_id())
+            .finish_non_exhaustive()
+    }
+}
+
 impl Repo {
     fn find_root(path: &Path) -> Option<&Path> {
         path.ancestors().find(|path| path.join(".jj").is_dir())
@@ -83,6 +198,10 @@ impl Repo {
         config_env.reload_user_config(&mut config)?;
         let config = config_env.resolve_config(&config)?;
 
+        let config_paths: Vec<PathBuf> =
+            config_env.user_config_paths().map(Path::to_owned).collect();
+        let config_mtimes = config_mtimes(&config_paths);
+
         let settings = UserSettings::from_config(config)?;
         let working_copy_factories = jj_lib::workspace::default_working_copy_factories();
         let workspace = Workspace::load(
@@ -102,12 +221,13 @@ impl Repo {
         let revset_extensions = Arc::new(RevsetExtensions::new());
         // TODO(config): user disambiguator
         let id_prefix_context = IdPrefixContext::new(Arc::clone(&revset_extensions));
-        id_prefix_context.populate(repo.base_repo())?;
+        let id_prefix_op_id = repo.op_id().clone();
 
         let template_aliases_map = load_template_aliases(settings.config())?;
 
         let default_ignored_remote = default_ignored_remote_name(workspace.repo_loader().store());
         let revsets_use_glob_by_default = settings.get("ui.revsets-use-glob-by-default")?;
+        let conflict_marker_style = settings.get("ui.conflict-marker-style")?;
 
         let mut this = Repo {
             repo,
@@ -115,12 +235,16 @@ impl Repo {
             settings,
             path_converter,
             id_prefix_context,
+            id_prefix_op_id,
             revset_aliases_map,
             revset_extensions,
             template_aliases_map,
             default_ignored_remote,
             revsets_use_glob_by_default,
+            conflict_marker_style,
             immutable_heads_expression: RevsetExpression::root(),
+            config_paths,
+            config_mtimes,
         };
 
         this.immutable_heads_expression = revset_util::parse_immutable_heads_expression(
@@ -128,14 +252,36 @@ impl Repo {
             &this.revset_parse_context(),
         )?;
 
-        this.id_prefix_context = match this.load_short_prefixes_expression()? {
-            Some(x) => this.id_prefix_context.disambiguate_within(x),
-            None => this.id_prefix_context,
-        };
+        this.id_prefix_context = this.load_id_prefix_context()?;
 
         Ok(Some(this))
     }
 
+    fn load_id_prefix_context(&self) -> Result<IdPrefixContext> {
+        let id_prefix_context = IdPrefixContext::new(Arc::clone(&self.revset_extensions));
+        Ok(match self.load_short_prefixes_expression()? {
+            Some(x) => id_prefix_context.disambiguate_within(x),
+            None => id_prefix_context,
+        })
+    }
+
+    /// Reloads the id prefix index if the repo changed since it was loaded. The index is loaded
+    /// lazily, and reused as long as the repo stays at the same operation.
+    fn update_id_prefix_context(&mut self) -> Result<()> {
+        if &self.id_prefix_op_id != self.repo.op_id() {
+            self.id_prefix_context = self.load_id_prefix_context()?;
+            self.id_prefix_op_id = self.repo.op_id().clone();
+        }
+
+        Ok(())
+    }
+
+    /// Whether the user config was edited since the repo was loaded, leaving the settings and
+    /// aliases outdated.
+    pub fn config_changed(&self) -> bool {
+        config_mtimes(&self.config_paths) != self.config_mtimes
+    }
+
     pub fn settings(&self) -> &UserSettings {
         &self.settings
     }
@@ -157,9 +303,54 @@ impl Repo {
         &self,
         settings_path: &'static str,
     ) -> Result<TemplateRenderer<'_, Commit>> {
-        let language = self.commit_template_language();
         let annotate_commit_summary_text = self.settings.get_string(settings_path)?;
-        let template = self.parse_template(&language, &annotate_commit_summary_text)?;
+        self.commit_template(&annotate_commit_summary_text)
+    }
+    pub fn commit_template(&self, template_text: &str) -> Result<TemplateRenderer<'_, Commit>> {
+        let language = self.commit_template_language();
+        let template = self.parse_template(&language, template_text)?;
+
+        Ok(template)
+    }
+    pub fn settings_node_template(
+        &self,
+        settings_path: &'static str,
+    ) -> Result<TemplateRenderer<'_, Option<Commit>>> {
+        let language = self.commit_template_language();
+        let template_text = self.settings.get_string(settings_path)?;
+        let template = self.parse_template(&language, &template_text)?;
+
+        Ok(template)
+    }
+    pub fn settings_operation_template(
+        &self,
+        settings_path: &'static str,
+    ) -> Result<TemplateRenderer<'static, Operation>> {
+        let extensions: &[Box<dyn OperationTemplateLanguageExtension>] = &[];
+        let language =
+            OperationTemplateLanguage::new(self.repo.loader(), Some(self.repo.op_id()), extensions);
+        let template_text = self.settings.get_string(settings_path)?;
+        let template = self.parse_template(&language, &template_text)?;
+
+        Ok(template)
+    }
+    pub fn settings_commit_ref_template(
+        &self,
+        settings_path: &'static str,
+    ) -> Result<TemplateRenderer<'_, Rc<CommitRef>>> {
+        let language = self.commit_template_language();
+        let template_text = self.settings.get_string(settings_path)?;
+        let template = self.parse_template(&language, &template_text)?;
+
+        Ok(template)
+    }
+    pub fn settings_evolog_template(
+        &self,
+        settings_path: &'static str,
+    ) -> Result<TemplateRenderer<'_, CommitEvolutionEntry>> {
+        let language = self.commit_template_language();
+        let template_text = self.settings.get_string(settings_path)?;
+        let template = self.parse_template(&language, &template_text)?;
 
         Ok(template)
     }
@@ -174,7 +365,14 @@ impl Repo {
         Ok(template)
     }
 
-    pub fn annotation(&self, starting_commit: &Commit, file_path: &str) -> Result<FileAnnotation> {
+    /// Annotates the lines of `file_path` in `starting_commit` with the commits that introduced
+    /// them. Fails once `cancelled` is set.
+    pub fn annotation(
+        &self,
+        starting_commit: &Commit,
+        file_path: &str,
+        cancelled: &AtomicBool,
+    ) -> Result<FileAnnotation> {
         let file_path = self.path_converter.parse_file_path(file_path)?;
         let file_value = starting_commit.tree().path_value(&file_path)?;
         let ui_path = self.path_converter.format_file_path(&file_path);
@@ -185,11 +383,23 @@ impl Repo {
             return Err(anyhow!("Path exists but is not a regular file: {ui_path}"));
         }
 
-        let domain = RevsetExpression::all();
-
+        // Search the ancestors a few generations at a time. The search leaves the commits where it
+        // stopped pending, and the next one continues from them.
         let mut annotator = FileAnnotator::from_commit(starting_commit, &file_path)?;
-        annotator.compute(self.repo.as_ref(), &domain)?;
-        annotator.to_annotation();
+        loop {
+            let pending = annotator.pending_commits().cloned().collect::<Vec<_>>();
+            if pending.is_empty() {
+                break;
+            }
+            ensure!(
+                !cancelled.load(Ordering::Relaxed),
+                "rendering was cancelled"
+            );
+
+            let domain =
+                RevsetExpression::commits(pending).ancestors_range(0..ANNOTATION_GENERATIONS);
+            annotator.compute(self.repo.as_ref(), &domain)?;
+        }
         let annotation = annotator.to_annotation();
 
         Ok(annotation)
@@ -197,7 +407,12 @@ impl Repo {
 
     pub fn log(&self) -> Result<Vec<Commit>> {
         let revset_string = self.settings.get_string("revsets.log")?;
-        let revset = self.revset_expression(&revset_string)?.evaluate()?;
+        self.revset_commits(&revset_string)
+    }
+
+    /// The commits of `revset_string`, newest first.
+    pub fn revset_commits(&self, revset_string: &str) -> Result<Vec<Commit>> {
+        let revset = self.revset_expression(revset_string)?.evaluate()?;
 
         let commits = revset
             .iter()
@@ -207,6 +422,189 @@ impl Repo {
         Ok(commits)
     }
 
+    /// The commits of `revset_string` in graph order, keeping the working copy's branch together
+    /// like `jj log`. Fails once `cancelled` is set.
+    pub fn log_graph(
+        &self,
+        revset_string: &str,
+        cancelled: &AtomicBool,
+    ) -> Result<Vec<GraphNode<CommitId>>> {
+        let revset = self.revset_expression(revset_string)?.evaluate()?;
+        let mut graph = TopoGroupedGraphIterator::new(revset.iter_graph(), |id| id);
+
+        let has_commit = revset.containing_fn();
+        if let Some(wc_commit_id) = self
+            .repo
+            .view()
+            .get_wc_commit_id(self.workspace.workspace_name())
+            && has_commit(wc_commit_id)?
+        {
+            graph.prioritize_branch(wc_commit_id.clone());
+        }
+
+        graph
+            .map(|node| {
+                ensure!(
+                    !cancelled.load(Ordering::Relaxed),
+                    "rendering was cancelled"
+                );
+                Ok(node?)
+            })
+            .collect()
+    }
+
+    /// The operations leading up to the current one, newest first.
+    pub fn operations(&self) -> Result<Vec<Operation>> {
+        let operations = op_walk::walk_ancestors(slice::from_ref(self.repo.operation()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(operations)
+    }
+
+    pub fn operation(&self, op_str: &str) -> Result<Operation> {
+        Ok(op_walk::resolve_op_with_repo(&self.repo, op_str)?)
+    }
+
+    pub fn operation_changes(&self, operation: &Operation) -> Result<OperationChanges> {
+        let mut parent_ops = operation.parents();
+        let Some(first_parent_op) = parent_ops.next().transpose()? else {
+            return Ok(OperationChanges {
+                added: Vec::new(),
+                removed: Vec::new(),
+                bookmarks: Vec::new(),
+            });
+        };
+
+        // Merge the views of several parents in memory, like `RepoLoader::merge_operations` but
+        // without writing the merged operation, since rendering a page mustn't change the repo.
+        let loader = self.repo.loader();
+        let mut parent_tx = loader.load_at(&first_parent_op)?.start_transaction();
+        for parent_op in parent_ops {
+            parent_tx.merge_operation(parent_op?)?;
+        }
+        let parent_view = parent_tx.repo().view();
+        let repo = loader.load_at(operation)?;
+
+        let heads = |view: &View| {
+            RevsetExpression::commits(view.heads().iter().cloned().collect()).ancestors()
+        };
+        let commits = |expression| -> Result<Vec<Commit>> {
+            let commits = RevsetExpressionEvaluator::new(
+                repo.as_ref(),
+                Arc::clone(&self.revset_extensions),
+                &self.id_prefix_context,
+                expression,
+            )
+            .evaluate_to_commits()?
+            .collect::<Result<Vec<_>, _>>()?;
+            Ok(commits)
+        };
+        let added = commits(heads(repo.view()).minus(&heads(parent_view)))?;
+        let removed = commits(heads(parent_view).minus(&heads(repo.view())))?;
+
+        let bookmarks = refs::diff_named_ref_targets(
+            parent_view.local_bookmarks(),
+            repo.view().local_bookmarks(),
+        )
+        .map(|(name, (before, after))| (name.to_owned(), before.clone(), after.clone()))
+        .collect();
+
+        Ok(OperationChanges {
+            added,
+            removed,
+            bookmarks,
+        })
+    }
+
+    /// Local bookmarks with their remote bookmarks, plus untracked remote bookmarks, sorted by
+    /// `ui.bookmark-list-sort-keys` like `jj bookmark list --all-remotes`.
+    pub fn bookmark_list(&self) -> Result<Vec<RefListItem>> {
+        let predicates = RefFilterPredicates {
+            name_matcher: StringMatcher::all(),
+            remote_matcher: StringMatcher::all(),
+            matched_local_targets: HashSet::new(),
+            conflicted: false,
+            include_local_only: true,
+            include_synced_remotes: true,
+            include_untracked_remotes: true,
+        };
+        let mut items = commit_ref_list::collect_items(self.repo.view().bookmarks(), &predicates);
+        let sort_keys = self.settings.get_value_with(
+            "ui.bookmark-list-sort-keys",
+            commit_ref_list::parse_sort_keys,
+        )?;
+        commit_ref_list::sort(self.repo.store(), &mut items, &sort_keys)?;
+
+        Ok(items)
+    }
+
+    /// Whether remote bookmarks of `remote` can be tracked or untracked, which isn't the case for
+    /// the refs of the colocated git repo.
+    pub fn is_trackable_remote(&self, remote: &str) -> bool {
+        self.default_ignored_remote
+            .is_none_or(|ignored| ignored.as_str() != remote)
+    }
+
+    /// The bookmark name `jj git push --change` would create for `commit`.
+    pub fn push_bookmark_name(&self, commit: &Commit) -> Result<String> {
+        let template = self.settings_commit_template("templates.git_push_bookmark")?;
+        let mut name = Vec::new();
+        template.format(commit, &mut PlainTextFormatter::new(&mut name))?;
+
+        Ok(String::from_utf8(name)?)
+    }
+
+    /// The previous versions of `commit`, starting with `commit` itself, like `jj evolog`.
+    pub fn evolution(&self, commit: &Commit) -> Result<Vec<CommitEvolutionEntry>> {
+        let entries = evolution::walk_predecessors(&self.repo, slice::from_ref(commit.id()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    /// The conflicted paths of `commit`, with their simplified conflicts.
+    pub fn conflicts(&self, commit: &Commit) -> Result<Vec<(RepoPathBuf, MergedTreeValue)>> {
+        commit
+            .tree()
+            .conflicts()
+            .map(|(path, conflict)| Ok((path, conflict?.simplify())))
+            .collect()
+    }
+
+    /// The commits of `revset_string` from oldest to newest, if they form a linear chain.
+    pub fn linear_commits(&self, revset_string: &str) -> Result<Vec<Commit>> {
+        let mut commits = self
+            .revset_expression(revset_string)?
+            .evaluate_to_commits()?
+            .collect::<Result<Vec<_>, _>>()?;
+        commits.reverse();
+
+        for pair in commits.windows(2) {
+            ensure!(
+                pair[1].parent_ids() == [pair[0].id().clone()],
+                "Only a linear chain of commits can be rebased, but {} is not the only child of {}",
+                pair[1].change_id(),
+                pair[0].change_id(),
+            );
+        }
+
+        Ok(commits)
+    }
+
+    pub fn children(&self, commit: &Commit) -> Result<Vec<Commit>> {
+        let expression = RevsetExpression::commit(commit.id().clone()).children();
+        let commits = RevsetExpressionEvaluator::new(
+            self.repo.as_ref(),
+            Arc::clone(&self.revset_extensions),
+            &self.id_prefix_context,
+            expression,
+        )
+        .evaluate_to_commits()?
+        .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(commits)
+    }
+
     pub fn revset_expression(&self, revset_string: &str) -> Result<RevsetExpressionEvaluator<'_>> {
         let mut diagnostics = RevsetDiagnostics::new();
         let context = self.revset_parse_context();
@@ -241,6 +639,908 @@ impl Repo {
         Ok(commit)
     }
 
+    /// Abandons `commit` and rebases its descendants onto its parents, like `jj abandon`.
+    pub fn abandon(&mut self, commit: &Commit) -> Result<()> {
+        let mut tx = self.start_transaction()?;
+        tx.check_rewritable([commit])?;
+
+        let options = RewriteRefsOptions {
+            delete_abandoned_bookmarks: true,
+        };
+        tx.repo_mut().transform_descendants_with_options(
+            vec![commit.id().clone()],
+            &HashMap::new(),
+            &options,
+            async |rewriter| {
+                if rewriter.old_commit().id() == commit.id() {
+                    rewriter.abandon();
+                } else {
+                    rewriter.rebase().await?.write()?;
+                }
+                Ok(())
+            },
+        )?;
+
+        tx.finish(format!("abandon commit {}", commit.id()))
+    }
+
+    /// Replaces the description of `commit`, like `jj describe`.
+    pub fn describe(&mut self, commit: &Commit, description: String) -> Result<()> {
+        if commit.description() == description {
+            return Ok(());
+        }
+
+        let mut tx = self.start_transaction()?;
+        tx.check_rewritable([commit])?;
+        tx.repo_mut()
+            .rewrite_commit(commit)
+            .set_description(description)
+            .write()?;
+
+        tx.finish(format!("describe commit {}", commit.id()))
+    }
+
+    /// Creates each of `bookmarks` pointing to its commit in a single operation, like
+    /// `jj bookmark create`.
+    pub fn create_bookmarks(&mut self, bookmarks: &[(RefNameBuf, Commit)]) -> Result<()> {
+        let mut tx = self.start_transaction()?;
+        for (name, _) in bookmarks {
+            ensure!(
+                tx.repo().view().get_local_bookmark(name).is_absent(),
+                "Bookmark {} already exists",
+                name.as_symbol()
+            );
+        }
+
+        for (name, commit) in bookmarks {
+            tx.repo_mut()
+                .set_local_bookmark_target(name, RefTarget::normal(commit.id().clone()));
+        }
+
+        let created = bookmarks
+            .iter()
+            .map(|(name, commit)| {
+                format!("{} pointing to commit {}", name.as_symbol(), commit.id())
+            })
+            .collect::<Vec<_>>();
+        tx.finish(format!("create bookmark {}", created.join(", ")))
+    }
+
+    /// Points the existing bookmark `name` to `commit`, like `jj bookmark move`. Like it without
+    /// `--allow-backwards`, `commit` must be a descendant of the bookmark's target, or of any of
+    /// its targets if it's conflicted.
+    pub fn move_bookmark(&mut self, name: &str, commit: &Commit) -> Result<()> {
+        let name = RefName::new(name);
+        let mut tx = self.start_transaction()?;
+        let old_target = tx.repo().view().get_local_bookmark(name);
+        ensure!(
+            old_target.is_present(),
+            "No such bookmark: {}",
+            name.as_symbol()
+        );
+        let mut is_fast_forward = false;
+        for old_id in old_target.added_ids() {
+            is_fast_forward |= tx.repo().index().is_ancestor(old_id, commit.id())?;
+        }
+        ensure!(
+            is_fast_forward,
+            "Refusing to move bookmark {} backwards or sideways",
+            name.as_symbol()
+        );
+
+        tx.repo_mut()
+            .set_local_bookmark_target(name, RefTarget::normal(commit.id().clone()));
+
+        tx.finish(format!(
+            "point bookmark {} to commit {}",
+            name.as_symbol(),
+            commit.id()
+        ))
+    }
+
+    /// Deletes the local bookmark `name`, which deletes it on tracking remotes on the next push,
+    /// like `jj bookmark delete`.
+    pub fn delete_bookmark(&mut self, name: &str) -> Result<()> {
+        let name = RefName::new(name);
+        let mut tx = self.start_transaction()?;
+        ensure!(
+            tx.repo().view().get_local_bookmark(name).is_present(),
+            "No such bookmark: {}",
+            name.as_symbol()
+        );
+        tx.repo_mut()
+            .set_local_bookmark_target(name, RefTarget::absent());
+
+        tx.finish(format!("delete bookmark {}", name.as_symbol()))
+    }
+
+    /// Forgets the local bookmark `name` and stops tracking its remote bookmarks, like
+    /// `jj bookmark forget`.
+    pub fn forget_bookmark(&mut self, name: &str) -> Result<()> {
+        let name = RefName::new(name);
+        let remotes: Vec<RemoteNameBuf> = self
+            .repo
+            .view()
+            .remote_bookmarks_matching(&StringMatcher::exact(name.as_str()), &StringMatcher::all())
+            .map(|(symbol, _)| symbol.remote.to_owned())
+            .filter(|remote| self.is_trackable_remote(remote.as_str()))
+            .collect();
+
+        let mut tx = self.start_transaction()?;
+        tx.repo_mut()
+            .set_local_bookmark_target(name, RefTarget::absent());
+        for remote in &remotes {
+            tx.repo_mut()
+                .untrack_remote_bookmark(name.to_remote_symbol(remote));
+        }
+
+        tx.finish(format!("forget bookmark {}", name.as_symbol()))
+    }
+
+    /// Starts tracking the remote bookmark `name@remote`, like `jj bookmark track`.
+    pub fn track_bookmark(&mut self, name: &str, remote: &str) -> Result<()> {
+        let symbol = RefName::new(name).to_remote_symbol(RemoteName::new(remote));
+        ensure!(
+            self.is_trackable_remote(remote),
+            "Git-tracking bookmark cannot be tracked: {symbol}"
+        );
+
+        let mut tx = self.start_transaction()?;
+        tx.repo_mut().track_remote_bookmark(symbol)?;
+
+        tx.finish(format!("track remote bookmark {symbol}"))
+    }
+
+    /// Stops tracking the remote bookmark `name@remote`, like `jj bookmark untrack`.
+    pub fn untrack_bookmark(&mut self, name: &str, remote: &str) -> Result<()> {
+        let symbol = RefName::new(name).to_remote_symbol(RemoteName::new(remote));
+        ensure!(
+            self.is_trackable_remote(remote),
+            "Git-tracking bookmark cannot be untracked: {symbol}"
+        );
+
+        let mut tx = self.start_transaction()?;
+        tx.repo_mut().untrack_remote_bookmark(symbol);
+
+        tx.finish(format!("untrack remote bookmark {symbol}"))
+    }
+
+    /// Reverts the changes made by `operation` in a new operation, like `jj op revert`.
+    pub fn revert_operation(&mut self, operation: &Operation) -> Result<()> {
+        let parent_ops = operation.parents().collect::<Result<Vec<_>, _>>()?;
+        let [parent_op] = parent_ops.as_slice() else {
+            return Err(anyhow!(
+                "Cannot revert {}",
+                if parent_ops.is_empty() {
+                    "the root operation"
+                } else {
+                    "a merge operation"
+                }
+            ));
+        };
+        let bad_repo = self.repo.loader().load_at(operation)?;
+        let parent_repo = self.repo.loader().load_at(parent_op)?;
+
+        let mut tx = self.start_transaction()?;
+        tx.repo_mut().merge(&bad_repo, &parent_repo)?;
+        let view = tx.repo().view().store_view().clone();
+        tx.restore_view(&view);
+
+        tx.finish(format!("revert operation {}", operation.id()))
+    }
+
+    /// Restores the repo to the state after `operation` in a new operation, like `jj op restore`.
+    pub fn restore_operation(&mut self, operation: &Operation) -> Result<()> {
+        let view = operation.view()?;
+
+        let mut tx = self.start_transaction()?;
+        tx.restore_view(view.store_view());
+
+        tx.finish(format!("restore to operation {}", operation.id()))
+    }
+
+    /// Creates a new empty commit on top of `parent` and checks it out, like `jj new`.
+    pub fn new_commit(&mut self, parent: &Commit) -> Result<Commit> {
+        let mut tx = self.start_transaction()?;
+        let new_commit = tx
+            .repo_mut()
+            .new_commit(vec![parent.id().clone()], parent.tree())
+            .write()?;
+        tx.edit(&new_commit)?;
+        tx.finish("new empty commit")?;
+
+        Ok(new_commit)
+    }
+
+    /// Makes `commit` the working-copy commit, like `jj edit`.
+    pub fn edit(&mut self, commit: &Commit) -> Result<()> {
+        let mut tx = self.start_transaction()?;
+        tx.check_rewritable([commit])?;
+        tx.edit(commit)?;
+
+        tx.finish(format!("edit commit {}", commit.id()))
+    }
+
+    /// Copies `commit` onto its parents with a new change id, like `jj duplicate`.
+    pub fn duplicate(&mut self, commit: &Commit) -> Result<Commit> {
+        let mut tx = self.start_transaction()?;
+        let stats =
+            duplicate_commits_onto_parents(tx.repo_mut(), &[commit.id().clone()], &HashMap::new())?;
+        let new_commit = stats
+            .duplicated_commits
+            .get(commit.id())
+            .cloned()
+            .ok_or_else(|| anyhow!("commit {} was not duplicated", commit.id()))?;
+
+        tx.finish(format!("duplicate commit {}", commit.id()))?;
+
+        Ok(new_commit)
+    }
+
+    /// Moves the working copy onto the next child or the previous parent of its parent, like
+    /// `jj next` and `jj prev`. With `ui.movement.edit`, edits the next child or previous parent
+    /// of the working-copy commit instead.
+    pub fn move_working_copy(&mut self, direction: Direction) -> Result<()> {
+        let current = self.current_commit()?;
+        let should_edit = self.settings.get_bool("ui.movement.edit")?;
+
+        let current_expression = RevsetExpression::commit(current.id().clone());
+        let start = if should_edit {
+            current_expression.clone()
+        } else {
+            ensure!(
+                self.children(&current)?.is_empty(),
+                "The working copy must not have any children"
+            );
+            current_expression.parents()
+        };
+        let expression = match direction {
+            Direction::Next if should_edit => start.children(),
+            Direction::Next => start.children().minus(&current_expression),
+            Direction::Prev => start.parents(),
+        };
+        let targets = RevsetExpressionEvaluator::new(
+            self.repo.as_ref(),
+            Arc::clone(&self.revset_extensions),
+            &self.id_prefix_context,
+            expression,
+        )
+        .evaluate_to_commits()?
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let target = match (targets.as_slice(), direction) {
+            ([target], _) => target,
+            ([], Direction::Next) => return Err(anyhow!("No next commit to move to")),
+            ([], Direction::Prev) => return Err(anyhow!("No previous commit to move to")),
+            (_, _) => {
+                return Err(anyhow!(
+                    "Ambiguous target, {} commits qualify; use Edit or New on one of them",
+                    targets.len()
+                ));
+            }
+        };
+
+        let mut tx = self.start_transaction()?;
+        if should_edit {
+            tx.check_rewritable([target])?;
+            tx.edit(target)?;
+        } else {
+            let new_commit = tx
+                .repo_mut()
+                .new_commit(vec![target.id().clone()], target.tree())
+                .write()?;
+            tx.edit(&new_commit)?;
+        }
+
+        tx.finish(format!(
+            "move working copy from {} to {}",
+            current.id(),
+            target.id()
+        ))
+    }
+
+    /// Returns the tree of `commit`'s parents with the changes of `commit` matched by `matcher`
+    /// applied.
+    pub fn select_paths(&self, commit: &Commit, matcher: &dyn Matcher) -> Result<MergedTree> {
+        let parent_tree = commit.parent_tree(self.repo.as_ref())?;
+        let selected_tree = futures_executor::block_on(restore_tree(
+            &commit.tree(),
+            &parent_tree,
+            commit.conflict_label(),
+            commit.parents_conflict_label()?,
+            matcher,
+        ))?;
+
+        Ok(selected_tree)
+    }
+
+    /// Returns the tree of `commit`'s parents with only `hunk` of the changes to `path` applied.
+    /// Fails if the lines of the hunk no longer match `checksum`, see [`DiffHunk::checksum`].
+    pub fn select_hunk(
+        &self,
+        commit: &Commit,
+        path: &RepoPath,
+        hunk: &DiffHunk,
+        checksum: &str,
+    ) -> Result<MergedTree> {
+        let parent_tree = commit.parent_tree(self.repo.as_ref())?;
+        let before = self.read_file(&parent_tree, path)?;
+        let after = self.read_file(&commit.tree(), path)?;
+        let selected = splice_hunk(&before.content, &after.content, hunk, checksum)?;
+
+        // Added and deleted files only exist in the selection while they have content.
+        let present = !selected.is_empty() || (before.value.is_some() && after.value.is_some());
+        let store = self.repo.store();
+        let value = match after.value.or(before.value) {
+            Some(TreeValue::File {
+                executable,
+                copy_id,
+                ..
+            }) if present => {
+                let id =
+                    futures_executor::block_on(store.write_file(path, &mut selected.as_slice()))?;
+                Merge::normal(TreeValue::File {
+                    id,
+                    executable,
+                    copy_id,
+                })
+            }
+            _ => Merge::absent(),
+        };
+
+        let mut builder = MergedTreeBuilder::new(parent_tree);
+        builder.set_or_remove(path.to_owned(), value);
+        Ok(builder.write_tree()?)
+    }
+
+    fn read_file(&self, tree: &MergedTree, path: &RepoPath) -> Result<FileContent> {
+        let value = tree.path_value(path)?;
+        let materialized = futures_executor::block_on(materialize_tree_value(
+            self.repo.store(),
+            path,
+            value.clone(),
+            tree.labels(),
+        ))?;
+
+        match materialized {
+            MaterializedTreeValue::Absent => Ok(FileContent {
+                value: None,
+                content: Vec::new(),
+            }),
+            MaterializedTreeValue::File(mut file) => Ok(FileContent {
+                value: value.into_resolved().ok().flatten(),
+                content: futures_executor::block_on(file.read_all(path))?,
+            }),
+            _ => Err(anyhow!(
+                "{} is not a regular file",
+                self.path_converter.format_file_path(path)
+            )),
+        }
+    }
+
+    /// Moves the `selected_tree` changes of `source` into `destination`, like `jj squash`.
+    pub fn squash(
+        &mut self,
+        source: &Commit,
+        destination: &Commit,
+        selected_tree: MergedTree,
+    ) -> Result<()> {
+        let mut tx = self.start_transaction()?;
+        tx.check_rewritable([source, destination])?;
+
+        let sources = [CommitWithSelection {
+            commit: source.clone(),
+            selected_tree,
+            parent_tree: source.parent_tree(tx.repo())?,
+        }];
+
+        let Some(squashed) = squash_commits(tx.repo_mut(), &sources, destination, false)? else {
+            return Err(anyhow!("No changes selected"));
+        };
+        let description = combine_descriptions(&squashed.abandoned_commits, destination);
+        squashed
+            .commit_builder
+            .set_description(description)
+            .write()?;
+
+        tx.finish(format!("squash commits into {}", destination.id()))
+    }
+
+    /// Moves `commit` to `destination` as described by `mode`, like `jj rebase`.
+    pub fn rebase(
+        &mut self,
+        commit: &Commit,
+        mode: RebaseMode,
+        destination: &Commit,
+    ) -> Result<()> {
+        ensure!(
+            commit.id() != destination.id(),
+            "Cannot rebase {} relative to itself",
+            commit.change_id()
+        );
+
+        let mut tx = self.start_transaction()?;
+        let commit_expression = RevsetExpression::commit(commit.id().clone());
+        let destination_expression = RevsetExpression::commit(destination.id().clone());
+
+        let (new_parent_ids, new_child_ids) = match mode {
+            RebaseMode::Revision | RebaseMode::Branch => (vec![destination.id().clone()], vec![]),
+            RebaseMode::InsertAfter => {
+                let children = destination_expression
+                    .children()
+                    .evaluate(tx.repo())?
+                    .iter()
+                    .collect::<Result<Vec<_>, _>>()?;
+                (vec![destination.id().clone()], children)
+            }
+            RebaseMode::InsertBefore => (
+                destination.parent_ids().to_vec(),
+                vec![destination.id().clone()],
+            ),
+        };
+
+        let target_ids = match mode {
+            RebaseMode::Branch => destination_expression
+                .range(&commit_expression)
+                .roots()
+                .evaluate(tx.repo())?
+                .iter()
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => vec![commit.id().clone()],
+        };
+        let target_commits = target_ids
+            .iter()
+            .chain(&new_child_ids)
+            .map(|id| tx.repo().store().get_commit(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        tx.check_rewritable(&target_commits)?;
+
+        if !new_child_ids.is_empty() {
+            let loop_commits = RevsetExpression::commits(new_child_ids.clone())
+                .intersection(&RevsetExpression::commits(new_parent_ids.clone()).ancestors())
+                .evaluate(tx.repo())?
+                .iter()
+                .next()
+                .transpose()?;
+            ensure!(
+                loop_commits.is_none(),
+                "Cannot insert {} there, it would create a loop",
+                commit.change_id()
+            );
+        }
+
+        let target = match mode {
+            RebaseMode::Branch => MoveCommitsTarget::Roots(target_ids),
+            _ => MoveCommitsTarget::Commits(target_ids),
+        };
+        move_commits(
+            tx.repo_mut(),
+            &MoveCommitsLocation {
+                new_parent_ids,
+                new_child_ids,
+                target,
+            },
+            &RebaseOptions::default(),
+        )?;
+
+        tx.finish(format!(
+            "rebase commit {} relative to {}",
+            commit.id(),
+            destination.id()
+        ))
+    }
+
+    /// Rebuilds a linear chain of commits onto `onto` in the order and with the actions of
+    /// `plan`, like `git rebase --interactive`.
+    pub fn rebase_plan(
+        &mut self,
+        onto: &[CommitId],
+        plan: &[(RebaseAction, Commit)],
+    ) -> Result<()> {
+        // Commits on top of the old head of the chain stay on top of the new head, wherever the
+        // plan moves the old head.
+        let old_head = plan.iter().map(|(_, commit)| commit).find(|commit| {
+            plan.iter()
+                .all(|(_, other)| !other.parent_ids().contains(commit.id()))
+        });
+        let outside_children = match old_head {
+            Some(old_head) => self
+                .children(old_head)?
+                .into_iter()
+                .filter(|child| plan.iter().all(|(_, commit)| commit.id() != child.id()))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let mut tx = self.start_transaction()?;
+        tx.check_rewritable(plan.iter().map(|(_, commit)| commit))?;
+
+        let mut parent_ids = onto.to_vec();
+        let mut previous: Option<Commit> = None;
+        for (action, commit) in plan {
+            if *action == RebaseAction::Drop {
+                tx.repo_mut().record_abandoned_commit(commit);
+                continue;
+            }
+
+            let rebased = if *action == RebaseAction::Squash {
+                let destination = previous.as_ref().ok_or_else(|| {
+                    anyhow!("Commit {} has no commit to squash into", commit.change_id())
+                })?;
+                let source = futures_executor::block_on(rebase_commit(
+                    tx.repo_mut(),
+                    commit.clone(),
+                    vec![destination.id().clone()],
+                ))?;
+                let sources = [CommitWithSelection {
+                    selected_tree: source.tree(),
+                    parent_tree: source.parent_tree(tx.repo())?,
+                    commit: source,
+                }];
+                let squashed = squash_commits(tx.repo_mut(), &sources, destination, false)?
+                    .ok_or_else(|| anyhow!("Failed to squash {}", commit.change_id()))?;
+                let description = combine_descriptions(&squashed.abandoned_commits, destination);
+                squashed
+                    .commit_builder
+                    .set_description(description)
+                    .write()?
+            } else if commit.parent_ids() == parent_ids {
+                commit.clone()
+            } else {
+                futures_executor::block_on(rebase_commit(
+                    tx.repo_mut(),
+                    commit.clone(),
+                    parent_ids.clone(),
+                ))?
+            };
+
+            let rebased = match action {
+                RebaseAction::Reword(subject) => {
+                    let description = match rebased.description().split_once('\n') {
+                        Some((_, body)) => format!("{subject}\n{body}"),
+                        None => format!("{subject}\n"),
+                    };
+                    tx.repo_mut()
+                        .rewrite_commit(&rebased)
+                        .set_description(description)
+                        .write()?
+                }
+                _ => rebased,
+            };
+
+            parent_ids = vec![rebased.id().clone()];
+            previous = Some(rebased);
+        }
+
+        if let Some(old_head) = old_head
+            && parent_ids != [old_head.id().clone()]
+        {
+            for child in outside_children {
+                let new_parent_ids = child
+                    .parent_ids()
+                    .iter()
+                    .flat_map(|id| {
+                        if id == old_head.id() {
+                            parent_ids.clone()
+                        } else {
+                            vec![id.clone()]
+                        }
+                    })
+                    .collect();
+                futures_executor::block_on(rebase_commit(tx.repo_mut(), child, new_parent_ids))?;
+            }
+        }
+
+        tx.finish(format!("rebase {} commits interactively", plan.len()))
+    }
+
+    /// Discards the `selected_tree` changes from `commit`, like `jj restore`.
+    pub fn restore(&mut self, commit: &Commit, selected_tree: MergedTree) -> Result<()> {
+        let mut tx = self.start_transaction()?;
+        tx.check_rewritable([commit])?;
+
+        let selection = CommitWithSelection {
+            commit: commit.clone(),
+            selected_tree,
+            parent_tree: commit.parent_tree(tx.repo())?,
+        };
+        ensure!(!selection.is_empty_selection(), "No changes selected");
+
+        let selected_diff = selection.diff_with_labels(
+            "parents of restored revision",
+            "selected changes to restore",
+            "restored revision",
+        )?;
+        let new_tree = futures_executor::block_on(MergedTree::merge(Merge::from_diffs(
+            (commit.tree(), commit.conflict_label()),
+            [selected_diff.invert()],
+        )))?;
+        tx.repo_mut()
+            .rewrite_commit(commit)
+            .set_tree(new_tree)
+            .write()?;
+
+        tx.finish(format!("restore into commit {}", commit.id()))
+    }
+
+    /// Replaces the contents of `path` in `commit` with `value`, like `jj resolve`.
+    pub fn resolve(
+        &mut self,
+        commit: &Commit,
+        path: &RepoPath,
+        value: MergedTreeValue,
+    ) -> Result<()> {
+        let mut tx = self.start_transaction()?;
+        tx.check_rewritable([commit])?;
+
+        let mut tree_builder = MergedTreeBuilder::new(commit.tree());
+        tree_builder.set_or_remove(path.to_owned(), value);
+        let new_tree = tree_builder.write_tree()?;
+        tx.repo_mut()
+            .rewrite_commit(commit)
+            .set_tree(new_tree)
+            .write()?;
+
+        tx.finish(format!("resolve conflicts in commit {}", commit.id()))
+    }
+
+    /// Moves the `selected_tree` changes of `commit` into a new commit before it, like `jj split`.
+    pub fn split(&mut self, commit: &Commit, selected_tree: MergedTree) -> Result<()> {
+        let mut tx = self.start_transaction()?;
+        tx.check_rewritable([commit])?;
+
+        let selection = CommitWithSelection {
+            commit: commit.clone(),
+            selected_tree,
+            parent_tree: commit.parent_tree(tx.repo())?,
+        };
+        ensure!(
+            !selection.is_empty_selection() && !selection.is_full_selection(),
+            "Splitting requires selecting some, but not all changes"
+        );
+
+        let first_commit = tx
+            .repo_mut()
+            .rewrite_commit(commit)
+            .set_tree(selection.selected_tree)
+            .write()?;
+        let second_commit = tx
+            .repo_mut()
+            .rewrite_commit(commit)
+            .clear_rewrite_source()
+            .generate_new_change_id()
+            .set_parents(vec![first_commit.id().clone()])
+            .set_tree(commit.tree())
+            .write()?;
+
+        tx.repo_mut()
+            .transform_descendants(vec![commit.id().clone()], async |mut rewriter| {
+                rewriter.replace_parent(first_commit.id(), [second_commit.id()]);
+                rewriter.rebase().await?.write()?;
+                Ok(())
+            })?;
+        for (name, wc_commit_id) in tx.repo().base_repo().clone().view().wc_commit_ids() {
+            if wc_commit_id == commit.id() {
+                tx.repo_mut().edit(name.clone(), &second_commit)?;
+            }
+        }
+
+        tx.finish(format!("split commit {}", commit.id()))
+    }
+
+    /// Starts a transaction on top of the latest operation.
+    ///
+    /// Like the jj CLI, this first picks up operations that were committed concurrently
+    /// (e.g. from a terminal) and snapshots the working copy. Finishing the transaction may check
+    /// out another commit, which overwrites the files on disk, so edits that weren't recorded
+    /// would be lost.
+    pub fn start_transaction(&mut self) -> Result<RepoTransaction<'_>> {
+        self.snapshot()?;
+
+        let tx = self.repo.start_transaction();
+        Ok(RepoTransaction { base: self, tx })
+    }
+
+    fn reload_at_head(&mut self) -> Result<()> {
+        let op_heads = futures_executor::block_on(self.repo.op_heads_store().get_op_heads())?;
+        if op_heads.as_slice() != [self.repo.op_id().clone()] {
+            if op_heads.len() > 1 {
+                log::info!("Concurrent modification detected, resolving automatically.");
+            }
+            self.repo = self.repo.reload_at_head()?;
+        }
+
+        Ok(())
+    }
+
+    /// Catches up with new operations, e.g. from `jj` in a terminal, and records the files on disk
+    /// in the working-copy commit, like `jj` does before every command. This way pages reading
+    /// `@` show edits that no command has picked up yet.
+    pub fn snapshot(&mut self) -> Result<()> {
+        self.reload_at_head()?;
+        self.check_working_copy_fresh()?;
+        let Ok(wc_commit) = self.current_commit() else {
+            return Ok(());
+        };
+
+        let auto_tracking_matcher = fileset::parse(
+            &mut FilesetDiagnostics::new(),
+            &self.settings.get_string("snapshot.auto-track")?,
+            &RepoPathUiConverter::Fs {
+                cwd: "".into(),
+                base: "".into(),
+            },
+        )?
+        .to_matcher();
+        let HumanByteSize(max_new_file_size) = self
+            .settings
+            .get_value_with("snapshot.max-new-file-size", TryInto::try_into)?;
+        let options = SnapshotOptions {
+            base_ignores: self.base_ignores()?,
+            progress: None,
+            start_tracking_matcher: &auto_tracking_matcher,
+            force_tracking_matcher: &NothingMatcher,
+            max_new_file_size: match max_new_file_size {
+                0 => u64::MAX,
+                size => size,
+            },
+        };
+
+        let workspace_name = self.workspace.workspace_name().to_owned();
+        let mut locked_ws = self.workspace.start_working_copy_mutation()?;
+        let (new_tree, _) = futures_executor::block_on(locked_ws.locked_wc().snapshot(&options))?;
+        if new_tree.tree_ids_and_labels() != wc_commit.tree().tree_ids_and_labels() {
+            let mut tx = self.repo.start_transaction();
+            tx.set_is_snapshot(true);
+            let commit = tx
+                .repo_mut()
+                .rewrite_commit(&wc_commit)
+                .set_tree(new_tree)
+                .write()?;
+            tx.repo_mut()
+                .set_wc_commit(workspace_name, commit.id().clone())?;
+            tx.repo_mut().rebase_descendants()?;
+            self.repo = tx.commit("snapshot working copy")?;
+        }
+        locked_ws.finish(self.repo.op_id().clone())?;
+
+        self.update_id_prefix_context()
+    }
+
+    /// The ignore patterns that apply on top of the `.gitignore` files in the working copy, like
+    /// git's `core.excludesFile` and `info/exclude`.
+    fn base_ignores(&self) -> Result<Arc<GitIgnoreFile>> {
+        let xdg_ignore_file = || {
+            std::env::var_os("XDG_CONFIG_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| std::env::home_dir().map(|home| home.join(".config")))
+                .map(|dir| dir.join("git").join("ignore"))
+        };
+
+        let mut ignores = GitIgnoreFile::empty();
+        if let Ok(git_backend) = git::get_git_backend(self.repo.store()) {
+            let excludes_file = git_backend
+                .git_repo()
+                .config_snapshot()
+                .string("core.excludesFile")
+                .and_then(|path| {
+                    let path = std::str::from_utf8(&path).ok()?;
+                    Some(self.workspace_dir().join(expand_home_path(path)))
+                })
+                .or_else(xdg_ignore_file);
+            if let Some(excludes_file) = excludes_file {
+                ignores = ignores.chain_with_file("", excludes_file)?;
+            }
+            ignores = ignores
+                .chain_with_file("", git_backend.git_repo_path().join("info").join("exclude"))?;
+        } else if let Some(excludes_file) = xdg_ignore_file() {
+            ignores = ignores.chain_with_file("", excludes_file)?;
+        }
+
+        Ok(ignores)
+    }
+
+    /// Whether snapshots ignore all the working-copy files at `paths`, relative to the workspace
+    /// root: they're untracked and matched by a `.gitignore` file or the base ignores.
+    pub fn ignores_all(&self, paths: &[&Path]) -> Result<bool> {
+        let tree = self.current_commit()?.tree();
+        // The ignores of each directory, shared by the paths inside of it.
+        let root_ignores = self
+            .base_ignores()?
+            .chain_with_file("", self.workspace_dir().join(".gitignore"))?;
+        let mut dir_ignores = HashMap::from([(String::new(), root_ignores)]);
+        for path in paths {
+            let repo_path = RepoPathBuf::from_relative_path(path)?;
+            let mut ignores = Arc::clone(&dir_ignores[""]);
+            let mut dir = String::new();
+            for component in repo_path
+                .parent()
+                .into_iter()
+                .flat_map(RepoPath::components)
+            {
+                dir.push_str(component.as_internal_str());
+                dir.push('/');
+                ignores = match dir_ignores.get(&dir) {
+                    Some(ignores) => Arc::clone(ignores),
+                    None => {
+                        let gitignore = self.workspace_dir().join(&dir).join(".gitignore");
+                        let ignores = ignores.chain_with_file(&dir, gitignore)?;
+                        dir_ignores.insert(dir.clone(), Arc::clone(&ignores));
+                        ignores
+                    }
+                };
+            }
+
+            // Ignored files that are already tracked are still snapshotted.
+            if !ignores.matches(repo_path.as_internal_file_string())
+                || tree.path_value(&repo_path)?.is_present()
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn check_working_copy_fresh(&mut self) -> Result<()> {
+        let Ok(wc_commit) = self.current_commit() else {
+            return Ok(());
+        };
+        let mut locked_ws = self.workspace.start_working_copy_mutation()?;
+        let freshness =
+            WorkingCopyFreshness::check_stale(locked_ws.locked_wc(), &wc_commit, &self.repo)?;
+        let wc_op_id = locked_ws.locked_wc().old_operation_id().clone();
+        drop(locked_ws);
+
+        match freshness {
+            WorkingCopyFreshness::Fresh => Ok(()),
+            WorkingCopyFreshness::Updated(wc_operation) => {
+                self.repo = self.repo.reload_at(&wc_operation)?;
+                Ok(())
+            }
+            WorkingCopyFreshness::WorkingCopyStale => Err(anyhow!(
+                "The working copy is stale (not updated since operation {wc_op_id:.12}), \
+                 run `jj workspace update-stale`"
+            )),
+            WorkingCopyFreshness::SiblingOperation => Err(anyhow!(
+                "The repo was loaded at operation {:.12}, which seems to be a sibling of the \
+                 working copy's operation {wc_op_id:.12}",
+                self.repo.op_id()
+            )),
+        }
+    }
+
+    pub fn is_immutable(&self, commit: &Commit) -> Result<bool> {
+        let immutable =
+            self.find_immutable_commit(self.repo.as_ref(), vec![commit.id().clone()])?;
+        Ok(immutable.is_some())
+    }
+
+    fn find_immutable_commit(
+        &self,
+        repo: &dyn jj_lib::repo::Repo,
+        commit_ids: Vec<CommitId>,
+    ) -> Result<Option<CommitId>> {
+        // The disambiguation index must not be cached against an arbitrary repo.
+        let id_prefix_context = IdPrefixContext::new(Arc::clone(&self.revset_extensions));
+        let immutable = RevsetExpressionEvaluator::new(
+            repo,
+            Arc::clone(&self.revset_extensions),
+            &id_prefix_context,
+            self.immutable_expression(),
+        )
+        .resolve()?;
+        let commit_id = immutable
+            .intersection(&RevsetExpression::commits(commit_ids))
+            .evaluate(repo)?
+            .iter()
+            .next()
+            .transpose()?;
+
+        Ok(commit_id)
+    }
+
     pub fn diff(&self, commit: &Commit) -> Result<DiffState<'_>> {
         let from_tree = commit.parent_tree(self.repo.as_ref())?;
         let to_tree = commit.tree();
@@ -266,6 +1566,21 @@ impl Repo {
         })
     }
 
+    /// The changes between `predecessors` rebased onto the parents of `commit`, and `commit`
+    /// itself, like `jj evolog --patch`.
+    pub fn inter_diff(&self, predecessors: &[Commit], commit: &Commit) -> Result<DiffState<'_>> {
+        let from_tree = rebase_to_dest_parent(self.repo.as_ref(), predecessors, commit)?;
+
+        Ok(DiffState {
+            repo: self,
+            copy_records: CopyRecords::default(),
+            tree: Diff {
+                before: from_tree,
+                after: commit.tree(),
+            },
+        })
+    }
+
     pub fn path_converter(&self) -> &RepoPathUiConverter {
         &self.path_converter
     }
@@ -299,7 +1614,15 @@ impl DiffState<'_> {
         Ok(())
     }
 
-    pub fn write_diff(&self, f: &mut dyn Formatter, matcher: &dyn Matcher) -> Result<()> {
+    /// Writes the diff in git format. Diffs that hunks are selected from must compare lines with
+    /// [`LineCompareMode::Exact`]: a selected hunk takes all its lines from the new file, so
+    /// whitespace changes shown as context would silently be selected along with it.
+    pub fn write_diff(
+        &self,
+        f: &mut dyn Formatter,
+        matcher: &dyn Matcher,
+        compare_mode: LineCompareMode,
+    ) -> Result<()> {
         let diff =
             self.tree
                 .before
@@ -312,17 +1635,106 @@ impl DiffState<'_> {
             self.tree.as_ref().map(|tree| tree.labels()),
             &UnifiedDiffOptions {
                 context: 3,
-                line_diff: diff_util::LineDiffOptions {
-                    compare_mode: LineCompareMode::IgnoreAllSpace,
-                },
+                line_diff: diff_util::LineDiffOptions { compare_mode },
             },
-            ConflictMarkerStyle::Git,
+            self.repo.conflict_marker_style,
         ))?;
 
         Ok(())
     }
 }
 
+impl RepoTransaction<'_> {
+    pub fn base(&self) -> &Repo {
+        self.base
+    }
+    pub fn repo(&self) -> &MutableRepo {
+        self.tx.repo()
+    }
+    pub fn repo_mut(&mut self) -> &mut MutableRepo {
+        self.tx.repo_mut()
+    }
+
+    pub fn check_rewritable<'c>(
+        &self,
+        commits: impl IntoIterator<Item = &'c Commit>,
+    ) -> Result<()> {
+        let commit_ids = commits.into_iter().map(|c| c.id().clone()).collect();
+        if let Some(commit_id) = self
+            .base
+            .find_immutable_commit(self.tx.repo(), commit_ids)?
+        {
+            let commit = self.tx.repo().store().get_commit(&commit_id)?;
+            return Err(anyhow!("Commit {} is immutable", commit.change_id()));
+        }
+
+        Ok(())
+    }
+
+    /// Sets the view to `view`, except for the git refs, which keep tracking the git repo.
+    fn restore_view(&mut self, view: &op_store::View) {
+        let current = self.tx.base_repo().view().store_view();
+        let view = op_store::View {
+            git_refs: current.git_refs.clone(),
+            git_head: current.git_head.clone(),
+            ..view.clone()
+        };
+        self.tx.repo_mut().set_view(view);
+    }
+
+    /// Makes `commit` the working-copy commit of this workspace, like `jj edit`.
+    pub fn edit(&mut self, commit: &Commit) -> Result<()> {
+        let workspace_name = self.base.workspace.workspace_name().to_owned();
+        self.tx.repo_mut().edit(workspace_name, commit)?;
+        Ok(())
+    }
+
+    /// Rebases descendants of rewritten commits, commits the operation and updates the
+    /// working copy on disk.
+    pub fn finish(mut self, description: impl Into<String>) -> Result<()> {
+        if !self.tx.repo().has_changes() {
+            return Ok(());
+        }
+
+        self.tx.repo_mut().rebase_descendants()?;
+
+        let workspace_name = self.base.workspace.workspace_name().to_owned();
+        if let Some(wc_commit_id) = self
+            .tx
+            .repo()
+            .view()
+            .get_wc_commit_id(&workspace_name)
+            .cloned()
+            && self
+                .base
+                .find_immutable_commit(self.tx.repo(), vec![wc_commit_id.clone()])?
+                .is_some()
+        {
+            let wc_commit = self.tx.repo().store().get_commit(&wc_commit_id)?;
+            self.tx.repo_mut().check_out(workspace_name, &wc_commit)?;
+        }
+
+        let old_wc_commit = self.base.current_commit().ok();
+        self.base.repo = self.tx.commit(description)?;
+        let new_wc_commit = self.base.current_commit().ok();
+
+        if let Some(new_wc_commit) = new_wc_commit
+            && old_wc_commit.as_ref().map(Commit::id) != Some(new_wc_commit.id())
+        {
+            self.base
+                .workspace
+                .check_out(
+                    self.base.repo.op_id().clone(),
+                    old_wc_commit.as_ref().map(Commit::tree).as_ref(),
+                    &new_wc_commit,
+                )
+                .with_context(|| format!("Failed to check out commit {}", new_wc_commit.id()))?;
+        }
+
+        self.base.update_id_prefix_context()
+    }
+}
+
 impl Repo {
     fn commit_template_language(&self) -> CommitTemplateLanguage<'_> {
         CommitTemplateLanguage::new(
@@ -332,8 +1744,7 @@ impl Repo {
             self.revset_parse_context(),
             &self.id_prefix_context,
             self.immutable_expression(),
-            ConflictMarkerStyle::Git,
-            // self.conflict_marker_style, TODO(config)
+            self.conflict_marker_style,
             // &self.command.data.commit_template_extensions,
             &[] as &[Arc<dyn CommitTemplateLanguageExtension>],
         )
@@ -468,6 +1879,28 @@ fn load_template_aliases(stacked_config: &StackedConfig) -> Result<TemplateAlias
     Ok(aliases_map)
 }
 
+/// The modification times of the config files at `paths`, including the files in config
+/// directories. Missing files have no time, so creating one counts as a change too.
+fn config_mtimes(paths: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mtime = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+
+    let mut mtimes = Vec::new();
+    for path in paths {
+        mtimes.push((path.clone(), mtime(path)));
+        if let Ok(entries) = path.read_dir() {
+            let mut files: Vec<PathBuf> = entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .collect();
+            files.sort();
+            mtimes.extend(files.into_iter().map(|file| {
+                let time = mtime(&file);
+                (file, time)
+            }));
+        }
+    }
+    mtimes
+}
+
 pub(super) fn evaluate_revset_to_single_commit(
     revision_str: &str,
     expression: &RevsetExpressionEvaluator<'_>,
@@ -481,3 +1914,211 @@ pub(super) fn evaluate_revset_to_single_commit(
         (Some(_), Some(_)) => Err(anyhow!("{revision_str} resolved to multiple commits")),
     }
 }
+
+/// The description of `destination` after squashing `sources` into it, like `jj squash` would
+/// offer in the editor.
+fn combine_descriptions(sources: &[Commit], destination: &Commit) -> String {
+    try_combine_messages(sources, destination).unwrap_or_else(|| {
+        let mut descriptions = vec![destination.description()];
+        descriptions.extend(sources.iter().map(Commit::description));
+        descriptions.join("\n")
+    })
+}
+
+/// `before` with the lines of `hunk` replaced by the corresponding lines of `after`. Fails if the
+/// lines of the hunk no longer match `checksum`, e.g. because the file changed since its diff was
+/// rendered.
+fn splice_hunk(before: &[u8], after: &[u8], hunk: &DiffHunk, checksum: &str) -> Result<Vec<u8>> {
+    let before_lines: Vec<&[u8]> = before.split_inclusive(|&b| b == b'\n').collect();
+    let after_lines: Vec<&[u8]> = after.split_inclusive(|&b| b == b'\n').collect();
+    ensure!(
+        hunk.before.end <= before_lines.len() && hunk.after.end <= after_lines.len(),
+        "Hunk {hunk} is out of date"
+    );
+    let text = |lines: &[&[u8]]| -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect()
+    };
+    ensure!(
+        DiffHunk::checksum(
+            text(&before_lines[hunk.before.clone()]),
+            text(&after_lines[hunk.after.clone()]),
+        ) == checksum,
+        "Hunk {hunk} is out of date"
+    );
+
+    Ok([
+        &before_lines[..hunk.before.start],
+        &after_lines[hunk.after.clone()],
+        &before_lines[hunk.before.end..],
+    ]
+    .concat()
+    .concat())
+}
+
+impl DiffHunk {
+    /// Identifies the contents of the old and new lines of a hunk, ignoring line endings. Hunks
+    /// are selected by line numbers computed when their diff was rendered, so the checksum makes
+    /// sure they are only applied while the file still has the lines that were shown.
+    pub fn checksum<B, A>(before: B, after: A) -> String
+    where
+        B: IntoIterator<Item: AsRef<str>>,
+        A: IntoIterator<Item: AsRef<str>>,
+    {
+        fn lines(lines: impl IntoIterator<Item: AsRef<str>>) -> Vec<String> {
+            lines
+                .into_iter()
+                .map(|line| line.as_ref().trim_end_matches(['\r', '\n']).to_owned())
+                .collect()
+        }
+
+        let hash = blake2b_hash(&(lines(before), lines(after)));
+        hex_util::encode_hex(&hash[..16])
+    }
+
+    /// Parses the ranges of a unified diff hunk header such as `@@ -1,3 +1,4 @@`.
+    pub fn parse(header: &str) -> Option<DiffHunk> {
+        let ranges = header
+            .trim()
+            .trim_start_matches("@@")
+            .trim_end_matches("@@");
+        let (before, after) = ranges.trim().split_once(' ')?;
+
+        fn parse_range(range: &str) -> Option<Range<usize>> {
+            let (start, len) = range.split_once(',')?;
+            let (start, len): (usize, usize) = (start.parse().ok()?, len.parse().ok()?);
+            // Empty ranges are numbered after the preceding line instead of the next one.
+            let start = if len == 0 {
+                start
+            } else {
+                start.checked_sub(1)?
+            };
+            Some(start..start + len)
+        }
+
+        Some(DiffHunk {
+            before: parse_range(before.strip_prefix('-')?)?,
+            after: parse_range(after.strip_prefix('+')?)?,
+        })
+    }
+}
+
+impl std::fmt::Display for DiffHunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn line_number(range: &Range<usize>) -> usize {
+            if range.is_empty() {
+                range.start
+            } else {
+                range.start + 1
+            }
+        }
+
+        write!(
+            f,
+            "-{},{} +{},{}",
+            line_number(&self.before),
+            self.before.len(),
+            line_number(&self.after),
+            self.after.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(before: Range<usize>, after: Range<usize>) -> DiffHunk {
+        DiffHunk { before, after }
+    }
+
+    fn splice(before: &str, after: &str, hunk: &DiffHunk) -> Result<String> {
+        let lines = |text: &str, range: &Range<usize>| -> Vec<String> {
+            text.split_inclusive('\n')
+                .skip(range.start)
+                .take(range.len())
+                .map(str::to_owned)
+                .collect()
+        };
+        let checksum = DiffHunk::checksum(lines(before, &hunk.before), lines(after, &hunk.after));
+        let spliced = splice_hunk(before.as_bytes(), after.as_bytes(), hunk, &checksum)?;
+        Ok(String::from_utf8(spliced).unwrap())
+    }
+
+    #[test]
+    fn parse_hunk_header() {
+        assert_eq!(DiffHunk::parse("@@ -1,3 +1,4 @@"), Some(hunk(0..3, 0..4)));
+        assert_eq!(
+            DiffHunk::parse("@@ -10,2 +12,0 @@\n"),
+            Some(hunk(9..11, 12..12))
+        );
+        assert_eq!(DiffHunk::parse("@@ -0,0 +1,2 @@"), Some(hunk(0..0, 0..2)));
+        assert_eq!(DiffHunk::parse("@@ -1,2 +0,0 @@"), Some(hunk(0..2, 0..0)));
+
+        assert_eq!(DiffHunk::parse("@@ -1 +1 @@"), None);
+        assert_eq!(DiffHunk::parse("@@ -0,1 +1,1 @@"), None);
+        assert_eq!(DiffHunk::parse("@@ +1,1 -1,1 @@"), None);
+        assert_eq!(DiffHunk::parse("diff --git a/file b/file"), None);
+    }
+
+    #[test]
+    fn hunk_header_round_trip() {
+        for header in [
+            "-1,3 +1,4",
+            "-10,2 +12,0",
+            "-0,0 +1,2",
+            "-1,2 +0,0",
+            "-5,0 +5,1",
+        ] {
+            let hunk = DiffHunk::parse(&format!("@@ {header} @@")).unwrap();
+            assert_eq!(hunk.to_string(), header);
+            assert_eq!(DiffHunk::parse(&format!("@@ {hunk} @@")), Some(hunk));
+        }
+    }
+
+    #[test]
+    fn splice_modified_file() {
+        let before = "a\nb\nc\nd\n";
+        let after = "a\nB\nc\nD\ne\n";
+
+        assert_eq!(
+            splice(before, after, &hunk(1..2, 1..2)).unwrap(),
+            "a\nB\nc\nd\n"
+        );
+        assert_eq!(
+            splice(before, after, &hunk(3..4, 3..5)).unwrap(),
+            "a\nb\nc\nD\ne\n"
+        );
+        assert_eq!(splice(before, after, &hunk(0..4, 0..5)).unwrap(), after);
+    }
+
+    #[test]
+    fn splice_added_and_deleted_file() {
+        assert_eq!(splice("", "a\nb\n", &hunk(0..0, 0..2)).unwrap(), "a\nb\n");
+        assert_eq!(splice("", "a\nb\n", &hunk(0..0, 1..2)).unwrap(), "b\n");
+        assert_eq!(splice("a\nb\n", "", &hunk(0..2, 0..0)).unwrap(), "");
+        assert_eq!(splice("a\nb\n", "", &hunk(0..1, 0..0)).unwrap(), "b\n");
+    }
+
+    #[test]
+    fn splice_empty_file_and_missing_newline() {
+        assert_eq!(splice("", "", &hunk(0..0, 0..0)).unwrap(), "");
+        assert_eq!(splice("a", "a\nb", &hunk(0..1, 0..2)).unwrap(), "a\nb");
+        assert_eq!(splice("a\n", "a", &hunk(0..1, 0..1)).unwrap(), "a");
+    }
+
+    #[test]
+    fn splice_out_of_date_hunk() {
+        let checksum = DiffHunk::checksum(["b\n"], ["B\n"]);
+        let hunk = hunk(1..2, 1..2);
+        assert!(splice_hunk(b"a\nb\n", b"a\nB\n", &hunk, &checksum).is_ok());
+        // The line of the hunk changed.
+        assert!(splice_hunk(b"a\nx\n", b"a\nB\n", &hunk, &checksum).is_err());
+        // A line was inserted before the hunk.
+        assert!(splice_hunk(b"a\nb\n", b"x\na\nB\n", &hunk, &checksum).is_err());
+        // The file is shorter than the hunk.
+        assert!(splice_hunk(b"a\n", b"a\nB\n", &hunk, &checksum).is_err());
+    }
+}
@@ -1,35 +1,96 @@
 #![allow(clippy::redundant_closure_call)]
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, anyhow};
 use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use jj_lib::diff::{ContentDiff, DiffHunkKind};
 use jjmagit_language_server::commands;
 use jjmagit_language_server::jj::Repo;
-use jjmagit_language_server::page_writer::{Page, PageWriter};
+use jjmagit_language_server::page_writer::{Editable, Page, PageWriter};
 use jjmagit_language_server::pages::{self};
 use jjmagit_language_server::semantic_token::LEGEND_TYPE;
 use log::{debug, trace};
 use ropey::Rope;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tokio::sync::RwLock;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock, watch};
+use tokio::task::JoinHandle;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::notification::Notification;
+use tower_lsp::lsp_types::request::Request;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 
+/// The choice of [`Backend::pick_revision`] that prompts for a revset.
+const OTHER_REVISION: &str = "Other revision...";
+/// How long a render may take before its progress is reported to the client.
+const PROGRESS_DELAY: Duration = Duration::from_millis(500);
+/// How long to wait for more file changes before refreshing the pages of a workspace, since e.g.
+/// a checkout or a build changes many files in a row.
+const REFRESH_DELAY: Duration = Duration::from_millis(200);
+
+/// A render of a page in progress, see [`Backend::render_page`].
 #[derive(Debug)]
+struct PendingRender {
+    cancelled: Arc<AtomicBool>,
+    /// Set once the render is done, whether it succeeded or not.
+    finished: watch::Sender<bool>,
+}
+
+/// The language server. Its state is shared between clones, so that background tasks can use it.
+#[derive(Debug, Clone)]
 struct Backend {
     client: Client,
-    document_map: DashMap<String, Rope>,
-    page_map: DashMap<String, Page>,
+    document_map: Arc<DashMap<String, Rope>>,
+    page_map: Arc<DashMap<String, Page>>,
+    /// The repos of the workspaces with open pages, keyed by workspace root.
+    repos: Arc<DashMap<PathBuf, Arc<Mutex<Repo>>>>,
+    /// The renders in progress, by page URI.
+    renders: Arc<DashMap<String, Arc<PendingRender>>>,
+    /// Whether the client shows `$/progress` notifications.
+    work_done_progress: Arc<AtomicBool>,
+    progress_tokens: Arc<AtomicU64>,
+    /// Whether the client lets the server register file watchers.
+    watch_files: Arc<AtomicBool>,
+    /// Whether the client handles [`ShowInputBox`] requests.
+    input_box: Arc<AtomicBool>,
+    /// The changed files of the workspaces whose pages are about to be refreshed, see
+    /// [`Backend::refresh_changed_files`].
+    pending_refreshes: Arc<DashMap<PathBuf, HashSet<PathBuf>>>,
 
-    workspace_folders: RwLock<Vec<Url>>,
+    workspace_folders: Arc<RwLock<Vec<Url>>>,
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let work_done_progress = params
+            .capabilities
+            .window
+            .and_then(|window| window.work_done_progress)
+            .unwrap_or(false);
+        self.work_done_progress
+            .store(work_done_progress, Ordering::Relaxed);
+        let watch_files = params
+            .capabilities
+            .workspace
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+        self.watch_files.store(watch_files, Ordering::Relaxed);
+        let input_box = params
+            .initialization_options
+            .as_ref()
+            .and_then(|options| options.get("showInputBox"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        self.input_box.store(input_box, Ordering::Relaxed);
+
         Ok(InitializeResult {
             server_info: None,
             offset_encoding: None,
@@ -48,7 +109,33 @@ impl LanguageServer for Backend {
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
                 completion_provider: None,
                 execute_command_provider: Some(ExecuteCommandOptions {
-                    commands: vec![commands::OPEN.to_string(), commands::TODO.to_string()],
+                    commands: vec![
+                        commands::OPEN.to_string(),
+                        commands::ABANDON.to_string(),
+                        commands::NEW.to_string(),
+                        commands::EDIT.to_string(),
+                        commands::DUPLICATE.to_string(),
+                        commands::NEXT.to_string(),
+                        commands::PREV.to_string(),
+                        commands::SQUASH.to_string(),
+                        commands::RESTORE.to_string(),
+                        commands::SPLIT.to_string(),
+                        commands::SQUASH_HUNK.to_string(),
+                        commands::SPLIT_HUNK.to_string(),
+                        commands::RESTORE_HUNK.to_string(),
+                        commands::CREATE_BOOKMARK.to_string(),
+                        commands::MOVE_BOOKMARK.to_string(),
+                        commands::DELETE_BOOKMARK.to_string(),
+                        commands::FORGET_BOOKMARK.to_string(),
+                        commands::TRACK_BOOKMARK.to_string(),
+                        commands::UNTRACK_BOOKMARK.to_string(),
+                        commands::UNDO_OPERATION.to_string(),
+                        commands::RESTORE_OPERATION.to_string(),
+                        commands::RESOLVE_CONFLICT.to_string(),
+                        commands::RESTORE_FILE.to_string(),
+                        commands::REBASE.to_string(),
+                        commands::TODO.to_string(),
+                    ],
                     work_done_progress_options: Default::default(),
                 }),
 
@@ -64,11 +151,19 @@ impl LanguageServer for Backend {
                         SemanticTokensRegistrationOptions {
                             text_document_registration_options: {
                                 TextDocumentRegistrationOptions {
-                                    document_selector: Some(vec![DocumentFilter {
-                                        language: Some("jjmagit".to_string()),
-                                        scheme: Some("file".to_string()),
-                                        pattern: None,
-                                    }]),
+                                    document_selector: Some(vec![
+                                        DocumentFilter {
+                                            language: Some("jjmagit".to_string()),
+                                            scheme: Some(pages::path::SCHEME.to_string()),
+                                            pattern: None,
+                                        },
+                                        // Editors that can only open files, see `parse_uri`.
+                                        DocumentFilter {
+                                            language: Some("jjmagit".to_string()),
+                                            scheme: Some("file".to_string()),
+                                            pattern: Some("**/.jj/**/*.jjmagit".to_string()),
+                                        },
+                                    ]),
                                 }
                             },
                             semantic_tokens_options: SemanticTokensOptions {
@@ -92,6 +187,10 @@ impl LanguageServer for Backend {
     }
     async fn initialized(&self, _: InitializedParams) {
         debug!("initialized!");
+
+        if let Err(e) = self.watch_repositories().await {
+            log::error!("Failed to register file watchers: {}", e);
+        }
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -101,12 +200,21 @@ impl LanguageServer for Backend {
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         debug!("file opened: {}", params.text_document.uri);
 
-        let item = TextDocumentItem {
-            uri: params.text_document.uri,
-            text: &params.text_document.text,
-            version: Some(params.text_document.version),
-        };
-        if let Err(e) = self.on_change(item).await {
+        let uri = params.text_document.uri;
+        let text = params.text_document.text;
+        self.document_map
+            .insert(uri.to_string(), Rope::from_str(&text));
+        // Pages are usually opened with the contents just rendered for `jjmagit/page`. Otherwise
+        // the opened contents may be outdated, e.g. for a page restored by the editor or opened
+        // as a file.
+        let rendered = self
+            .page_map
+            .get(uri.as_str())
+            .is_some_and(|page| page.text == text);
+        if rendered {
+            return;
+        }
+        if let Err(e) = self.refresh_page(&uri, true).await {
             log::error!("Error during did_open: {}", e);
         }
     }
@@ -127,20 +235,41 @@ impl LanguageServer for Backend {
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
         debug!("file saved!");
         if let Some(text) = params.text {
-            let item = TextDocumentItem {
-                uri: params.text_document.uri,
-                text: &text,
-                version: None,
+            let uri = params.text_document.uri;
+            self.document_map
+                .insert(uri.to_string(), Rope::from_str(&text));
+
+            let result = match self.apply_edits(&uri, &text).await {
+                Ok(()) => self.refresh_page(&uri, true).await,
+                Err(e) => {
+                    log::error!("Error applying edits: {}", e);
+                    self.client
+                        .show_message(MessageType::ERROR, format!("Failed to save page: {e}"))
+                        .await;
+
+                    // Keep the saved contents in the editor, so the errors can be fixed.
+                    let item = TextDocumentItem {
+                        uri,
+                        text: &text,
+                        version: None,
+                    };
+                    self.on_change(item).await
+                }
             };
-            if let Err(e) = self.on_change(item).await {
+            if let Err(e) = result {
                 log::error!("Error during did_save: {}", e);
             }
 
             _ = self.client.semantic_tokens_refresh().await;
         }
     }
-    async fn did_close(&self, _: DidCloseTextDocumentParams) {
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
         debug!("file closed!");
+
+        // Closed pages no longer need to be kept up to date.
+        let uri = params.text_document.uri.to_string();
+        self.document_map.remove(&uri);
+        self.page_map.remove(&uri);
     }
 
     async fn goto_definition(
@@ -170,7 +299,7 @@ impl LanguageServer for Backend {
                         start: start_position,
                         end: end_position,
                     }),
-                    target_uri: target.target.clone(),
+                    target_uri: pages::path::with_scheme_of(&target.target, &uri),
                     target_range,
                     target_selection_range: target_range,
                 }]))
@@ -342,11 +471,23 @@ impl LanguageServer for Backend {
             let rope = self.document_map.get(uri.as_str())?;
             let action_range = range_to_offset(params.range, &rope)?;
 
-            let code_actions = page
-                .code_actions
+            // A selection, rather than a cursor, can pick individual lines of a diff.
+            let line_actions = if action_range.is_empty() {
+                Vec::new()
+            } else {
+                let (workspace, _, _) = pages::path::parse_uri(&uri).ok()?;
+                page.line_actions(&workspace, &action_range)
+            };
+
+            let code_actions = line_actions
                 .iter()
-                .filter(|(range, _)| intersects(&action_range, range))
-                .map(|(_range, action)| {
+                .chain(
+                    page.code_actions
+                        .iter()
+                        .filter(|(range, _)| intersects(&action_range, range))
+                        .map(|(_range, action)| action),
+                )
+                .map(|action| {
                     CodeActionOrCommand::Command(Command {
                         title: action.title.clone(),
                         command: action.command.to_owned(),
@@ -529,8 +670,34 @@ impl LanguageServer for Backend {
         workspace_folders.extend(params.event.added.into_iter().map(|added| added.uri));
     }
 
-    async fn did_change_watched_files(&self, _: DidChangeWatchedFilesParams) {
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
         debug!("watched files have changed!");
+
+        let changed_paths: Vec<_> = params
+            .changes
+            .iter()
+            .filter_map(|change| change.uri.to_file_path().ok())
+            .collect();
+        for workspace in self.open_workspaces() {
+            let paths = changed_paths
+                .iter()
+                .filter(|path| path.starts_with(&workspace))
+                .cloned();
+            // Changes that arrive while a refresh is pending are checked along with it.
+            match self.pending_refreshes.entry(workspace.clone()) {
+                Entry::Occupied(mut pending) => pending.get_mut().extend(paths),
+                Entry::Vacant(pending) => {
+                    let paths: HashSet<_> = paths.collect();
+                    if paths.is_empty() {
+                        continue;
+                    }
+                    pending.insert(paths);
+
+                    let backend = self.clone();
+                    tokio::spawn(async move { backend.refresh_changed_files(workspace).await });
+                }
+            }
+        }
     }
 
     async fn execute_command(&self, command: ExecuteCommandParams) -> Result<Option<Value>> {
@@ -555,12 +722,307 @@ impl LanguageServer for Backend {
                     .ok_or_else(|| anyhow!("wrong parameter page {:?}", page))?;
                 let argument = value_as_option(file_path)
                     .map(|x| x.as_str().context("invalid parameter file_path"))
-                    .transpose()?
-                    .unwrap_or("");
+                    .transpose()?;
 
-                jjmagit_language_server::commands::open_page(workspace, page, &[argument])
-                    .await
-                    .map(|p| Value::String(p.to_str().unwrap().to_owned()))
+                let uri = self
+                    .with_repo(workspace, |repo| {
+                        Ok(commands::open_page(repo, page, argument.as_slice()))
+                    })
+                    .await?;
+
+                Ok(Value::String(uri.to_string()))
+            }
+            commands::ABANDON => {
+                let (workspace, change_id) = workspace_and_change_id(&command)?;
+                self.with_repo(workspace, |repo| commands::abandon(repo, change_id))
+                    .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::NEW => {
+                let (workspace, change_id) = workspace_and_change_id(&command)?;
+                self.with_repo(workspace, |repo| commands::new(repo, change_id))
+                    .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::EDIT => {
+                let (workspace, change_id) = workspace_and_change_id(&command)?;
+                self.with_repo(workspace, |repo| commands::edit(repo, change_id))
+                    .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::DUPLICATE => {
+                let (workspace, change_id) = workspace_and_change_id(&command)?;
+                self.with_repo(workspace, |repo| commands::duplicate(repo, change_id))
+                    .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::NEXT | commands::PREV => {
+                let arguments = str_arguments(&command)?;
+                let [workspace] = arguments.as_slice() else {
+                    return Err(anyhow!(
+                        "wrong arguments to command {}: {:?}",
+                        command.command,
+                        command.arguments
+                    ));
+                };
+                let workspace = Path::new(workspace);
+                self.with_repo(workspace, |repo| {
+                    commands::move_working_copy(repo, &command.command)
+                })
+                .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::SQUASH => {
+                let arguments = str_arguments(&command)?;
+                let [workspace, source, destination, paths @ ..] = arguments.as_slice() else {
+                    return Err(anyhow!(
+                        "wrong arguments to command {}: {:?}",
+                        commands::SQUASH,
+                        command.arguments
+                    ));
+                };
+                let workspace = Path::new(workspace);
+                self.with_repo(workspace, |repo| {
+                    commands::squash(repo, source, destination, paths)
+                })
+                .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::SPLIT => {
+                let arguments = str_arguments(&command)?;
+                let [workspace, change_id, position, paths @ ..] = arguments.as_slice() else {
+                    return Err(anyhow!(
+                        "wrong arguments to command {}: {:?}",
+                        commands::SPLIT,
+                        command.arguments
+                    ));
+                };
+                let workspace = Path::new(workspace);
+                self.with_repo(workspace, |repo| {
+                    commands::split(repo, change_id, position, paths)
+                })
+                .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::RESTORE => {
+                let arguments = str_arguments(&command)?;
+                let [workspace, change_id, paths @ ..] = arguments.as_slice() else {
+                    return Err(anyhow!(
+                        "wrong arguments to command {}: {:?}",
+                        commands::RESTORE,
+                        command.arguments
+                    ));
+                };
+                let workspace = Path::new(workspace);
+                let message = format!(
+                    "Discard the changes to {} in {change_id:.12}?",
+                    paths.join(", ")
+                );
+                if !self.confirm(message, "Discard").await? {
+                    return Ok(Value::Null);
+                }
+                self.with_repo(workspace, |repo| commands::restore(repo, change_id, paths))
+                    .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::SQUASH_HUNK => {
+                let arguments = str_arguments(&command)?;
+                let [workspace, source, destination, path, hunk, checksum] = arguments.as_slice()
+                else {
+                    return Err(anyhow!(
+                        "wrong arguments to command {}: {:?}",
+                        commands::SQUASH_HUNK,
+                        command.arguments
+                    ));
+                };
+                let workspace = Path::new(workspace);
+                let destination = match *destination {
+                    "" => match self.pick_revision(workspace, source, true).await? {
+                        Some(destination) => destination,
+                        None => return Ok(Value::Null),
+                    },
+                    destination => destination.to_owned(),
+                };
+                self.with_repo(workspace, |repo| {
+                    commands::squash_hunk(repo, source, &destination, path, hunk, checksum)
+                })
+                .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::SPLIT_HUNK | commands::RESTORE_HUNK => {
+                let arguments = str_arguments(&command)?;
+                let [workspace, change_id, path, hunk, checksum] = arguments.as_slice() else {
+                    return Err(anyhow!(
+                        "wrong arguments to command {}: {:?}",
+                        command.command,
+                        command.arguments
+                    ));
+                };
+                let workspace = Path::new(workspace);
+                if command.command == commands::SPLIT_HUNK {
+                    self.with_repo(workspace, |repo| {
+                        commands::split_hunk(repo, change_id, path, hunk, checksum)
+                    })
+                    .await?;
+                } else {
+                    let message = format!("Discard hunk {hunk} of {path} in {change_id:.12}?");
+                    if !self.confirm(message, "Discard").await? {
+                        return Ok(Value::Null);
+                    }
+                    self.with_repo(workspace, |repo| {
+                        commands::restore_hunk(repo, change_id, path, hunk, checksum)
+                    })
+                    .await?;
+                }
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::CREATE_BOOKMARK => {
+                let (workspace, change_id) = workspace_and_change_id(&command)?;
+                self.with_repo(workspace, |repo| commands::create_bookmark(repo, change_id))
+                    .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::DELETE_BOOKMARK | commands::FORGET_BOOKMARK => {
+                let (workspace, name) = workspace_and_change_id(&command)?;
+                if command.command == commands::DELETE_BOOKMARK {
+                    self.with_repo(workspace, |repo| commands::delete_bookmark(repo, name))
+                        .await?;
+                } else {
+                    self.with_repo(workspace, |repo| commands::forget_bookmark(repo, name))
+                        .await?;
+                }
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::MOVE_BOOKMARK | commands::TRACK_BOOKMARK | commands::UNTRACK_BOOKMARK => {
+                let arguments = str_arguments(&command)?;
+                let [workspace, name, argument] = arguments.as_slice() else {
+                    return Err(anyhow!(
+                        "wrong arguments to command {}: {:?}",
+                        command.command,
+                        command.arguments
+                    ));
+                };
+                let workspace = Path::new(workspace);
+                match command.command.as_str() {
+                    commands::MOVE_BOOKMARK => {
+                        self.with_repo(workspace, |repo| {
+                            commands::move_bookmark(repo, name, argument)
+                        })
+                        .await?
+                    }
+                    commands::TRACK_BOOKMARK => {
+                        self.with_repo(workspace, |repo| {
+                            commands::track_bookmark(repo, name, argument)
+                        })
+                        .await?
+                    }
+                    _ => {
+                        self.with_repo(workspace, |repo| {
+                            commands::untrack_bookmark(repo, name, argument)
+                        })
+                        .await?
+                    }
+                }
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::UNDO_OPERATION => {
+                let (workspace, op_id) = workspace_and_change_id(&command)?;
+                self.with_repo(workspace, |repo| commands::undo_operation(repo, op_id))
+                    .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::RESTORE_OPERATION => {
+                let (workspace, op_id) = workspace_and_change_id(&command)?;
+                self.with_repo(workspace, |repo| commands::restore_operation(repo, op_id))
+                    .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::RESOLVE_CONFLICT | commands::RESTORE_FILE => {
+                let arguments = str_arguments(&command)?;
+                let [workspace, change_id, first, second] = arguments.as_slice() else {
+                    return Err(anyhow!(
+                        "wrong arguments to command {}: {:?}",
+                        command.command,
+                        command.arguments
+                    ));
+                };
+                let workspace = Path::new(workspace);
+                if command.command == commands::RESOLVE_CONFLICT {
+                    self.with_repo(workspace, |repo| {
+                        commands::resolve_conflict(repo, change_id, first, second)
+                    })
+                    .await?;
+                } else {
+                    let message = format!(
+                        "Discard the changes to {second} in {change_id:.12} and restore it from \
+                         {first:.12}?"
+                    );
+                    if !self.confirm(message, "Restore").await? {
+                        return Ok(Value::Null);
+                    }
+                    self.with_repo(workspace, |repo| {
+                        commands::restore_file(repo, change_id, first, second)
+                    })
+                    .await?;
+                }
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
+            }
+            commands::REBASE => {
+                let arguments = str_arguments(&command)?;
+                let [workspace, change_id, mode, destination] = arguments.as_slice() else {
+                    return Err(anyhow!(
+                        "wrong arguments to command {}: {:?}",
+                        commands::REBASE,
+                        command.arguments
+                    ));
+                };
+                let workspace = Path::new(workspace);
+                let destination = match *destination {
+                    "" => match self.pick_revision(workspace, change_id, false).await? {
+                        Some(destination) => destination,
+                        None => return Ok(Value::Null),
+                    },
+                    destination => destination.to_owned(),
+                };
+                self.with_repo(workspace, |repo| {
+                    commands::rebase_commit(repo, change_id, mode, &destination)
+                })
+                .await?;
+                self.refresh_pages(workspace).await?;
+
+                Ok(Value::Null)
             }
             commands::TODO => {
                 self.client
@@ -577,6 +1039,12 @@ impl LanguageServer for Backend {
             Ok(res) => Ok(Some(res)),
             Err(e) => {
                 log::error!("failed to run command: {e}");
+                self.client
+                    .show_message(
+                        MessageType::ERROR,
+                        format!("Failed to run {}: {e}", command.command),
+                    )
+                    .await;
                 Ok(None)
             }
         }
@@ -589,6 +1057,25 @@ struct InlayHintParams {
     path: String,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+struct PageParams {
+    uri: Url,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ShowInputBoxParams {
+    prompt: String,
+}
+
+/// Asks the user for a line of text, or `null` if they cancelled. Only sent to clients that set
+/// `showInputBox` in their initialization options.
+enum ShowInputBox {}
+impl Request for ShowInputBox {
+    type Params = ShowInputBoxParams;
+    type Result = Option<String>;
+    const METHOD: &'static str = "jjmagit/showInputBox";
+}
+
 #[allow(unused)]
 enum CustomNotification {}
 impl Notification for CustomNotification {
@@ -603,26 +1090,55 @@ struct TextDocumentItem<'a> {
 }
 
 impl Backend {
+    /// Handles `jjmagit/page`, which returns the contents of the page at a `jjmagit:` URI.
+    async fn page_text(&self, params: PageParams) -> Result<String> {
+        debug!("page requested: {}", params.uri);
+
+        // Pages are only kept up to date while they're open.
+        if !self.document_map.contains_key(params.uri.as_str()) {
+            self.page_map.remove(params.uri.as_str());
+        }
+
+        loop {
+            if let Some(page) = self.page_map.get(params.uri.as_str()) {
+                return Ok(page.text.clone());
+            }
+
+            match self.render_page(&params.uri, true).await {
+                Ok(Some(page)) => {
+                    let text = page.text.clone();
+                    // The page is usually opened next, which can then skip rendering it again.
+                    self.page_map.insert(params.uri.to_string(), page);
+                    return Ok(text);
+                }
+                // A newer render superseded this one. Once it's done, its page is either kept
+                // for the open page, or the page is rendered again.
+                Ok(None) => self.wait_for_render(&params.uri).await,
+                Err(e) => {
+                    log::error!("Error rendering {}: {}", params.uri, e);
+                    return Err(tower_lsp::jsonrpc::Error {
+                        message: e.to_string().into(),
+                        ..tower_lsp::jsonrpc::Error::internal_error()
+                    });
+                }
+            }
+        }
+    }
+
     async fn on_change(&self, params: TextDocumentItem<'_>) -> anyhow::Result<()> {
         let rope = ropey::Rope::from_str(params.text);
         self.document_map.insert(params.uri.to_string(), rope);
 
-        let page_path = params
-            .uri
-            .to_file_path()
-            .map_err(|()| anyhow!("Expected path, got url"))?;
-        let (repo_path, page, arguments) = pages::path::parse_path(&page_path)?;
-        let arguments: Vec<_> = arguments.iter().map(String::as_str).collect();
-
-        let repo = Repo::detect(&repo_path)?.ok_or_else(|| anyhow!("no jj root found"))?;
-
-        let mut out = PageWriter::default();
-        page.render(&mut out, &repo, &arguments)?;
-        let page = out.finish();
-        if let Some(parent) = page_path.parent() {
-            std::fs::create_dir_all(&parent)?;
+        // Keep the page the buffer was rendered from: the saved buffer's edits are found by
+        // comparing with it, and it tells whether the buffer has unsaved edits.
+        if self.page_map.contains_key(params.uri.as_str()) {
+            return Ok(());
         }
-        std::fs::write(page_path, &page.text)?;
+
+        // Editing the page doesn't change the working copy, so there is no need to snapshot it.
+        let Some(page) = self.render_page(&params.uri, false).await? else {
+            return Ok(());
+        };
 
         let changed = page.text != params.text;
         debug!("on_change regenerated a different file: {}", changed);
@@ -631,6 +1147,449 @@ impl Backend {
 
         Ok(())
     }
+
+    /// Applies the changes made to the editable regions of a saved page to the repo.
+    async fn apply_edits(&self, uri: &Url, text: &str) -> anyhow::Result<()> {
+        let edits: Vec<(Editable, String, usize)> = {
+            let page = self
+                .page_map
+                .get(uri.as_str())
+                .ok_or_else(|| anyhow!("page {uri} is not open"))?;
+            page.edits(text)?
+                .into_iter()
+                .map(|(editable, offset, edited)| (editable.clone(), edited.to_owned(), offset))
+                .collect()
+        };
+        if edits.is_empty() {
+            // Saving the page as rendered clears the errors of an earlier save.
+            self.client
+                .publish_diagnostics(uri.clone(), Vec::new(), None)
+                .await;
+            return Ok(());
+        }
+
+        let (workspace, _, _) = pages::path::parse_uri(uri)?;
+        let mut diagnostics = Vec::new();
+        let mut result = Ok(());
+        for (editable, edited, offset) in edits {
+            result = match editable {
+                Editable::Description { change_id } => {
+                    self.with_repo(&workspace, |repo| {
+                        commands::describe(repo, &change_id, &edited)
+                    })
+                    .await
+                }
+                Editable::NewBookmarks => {
+                    self.with_repo(&workspace, |repo| commands::create_bookmarks(repo, &edited))
+                        .await
+                }
+                Editable::RebasePlan { change_ids } => {
+                    self.with_repo(&workspace, |repo| {
+                        commands::rebase(repo, &change_ids, &edited)
+                    })
+                    .await
+                }
+            };
+            if let Err(e) = &result {
+                if let Some(commands::LineErrors(errors)) = e.downcast_ref() {
+                    let rope = Rope::from_str(text);
+                    diagnostics = line_diagnostics(&rope, &edited, offset, errors);
+                }
+                break;
+            }
+        }
+        self.client
+            .publish_diagnostics(uri.clone(), diagnostics, None)
+            .await;
+        result?;
+
+        self.refresh_pages(&workspace).await
+    }
+
+    /// Asks the client to notify the server of new operations and of changes to working-copy
+    /// files, so open pages can be refreshed when the repository changes outside the editor.
+    async fn watch_repositories(&self) -> anyhow::Result<()> {
+        if !self.watch_files.load(Ordering::Relaxed) {
+            debug!("the client can't watch files, pages only refresh after commands");
+            return Ok(());
+        }
+
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![
+                FileSystemWatcher {
+                    glob_pattern: GlobPattern::String("**/.jj/repo/op_heads/heads/*".to_owned()),
+                    kind: None,
+                },
+                FileSystemWatcher {
+                    glob_pattern: GlobPattern::String("**/*".to_owned()),
+                    kind: None,
+                },
+            ],
+        };
+
+        self.client
+            .register_capability(vec![Registration {
+                id: "jjmagit-watched-files".to_owned(),
+                method: notification::DidChangeWatchedFiles::METHOD.to_owned(),
+                register_options: Some(serde_json::to_value(options)?),
+            }])
+            .await?;
+
+        Ok(())
+    }
+
+    /// The workspaces that have open pages.
+    fn open_workspaces(&self) -> Vec<PathBuf> {
+        let mut workspaces = Vec::new();
+        for entry in self.document_map.iter() {
+            let Some(workspace) = Url::parse(entry.key())
+                .ok()
+                .and_then(|uri| pages::path::parse_uri(&uri).ok())
+                .map(|(workspace, _, _)| workspace)
+            else {
+                continue;
+            };
+            if !workspaces.contains(&workspace) {
+                workspaces.push(workspace);
+            }
+        }
+        workspaces
+    }
+
+    /// Refreshes the pages of `workspace` [`REFRESH_DELAY`] after files in it changed, unless the
+    /// changes can't affect them, see [`is_repo_change`].
+    async fn refresh_changed_files(&self, workspace: PathBuf) {
+        tokio::time::sleep(REFRESH_DELAY).await;
+        let Some((_, paths)) = self.pending_refreshes.remove(&workspace) else {
+            return;
+        };
+
+        let paths: Vec<_> = paths.into_iter().collect();
+        let is_change = match self.repo(&workspace).await {
+            Ok(repo) => is_repo_change(&*repo.lock().await, &paths),
+            Err(e) => Err(e),
+        };
+        let result = match is_change {
+            Ok(true) => self.refresh_pages(&workspace).await,
+            Ok(false) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            log::error!("Error refreshing pages of {}: {}", workspace.display(), e);
+        }
+    }
+
+    /// Re-renders every open page belonging to `workspace`, e.g. after a command changed the repo.
+    async fn refresh_pages(&self, workspace: &Path) -> anyhow::Result<()> {
+        // Snapshot once up front rather than once per page.
+        self.repo(workspace).await?.lock().await.snapshot()?;
+
+        let uris: Vec<String> = self.document_map.iter().map(|e| e.key().clone()).collect();
+        for uri in uris {
+            let uri = Url::parse(&uri)?;
+            let in_workspace = pages::path::parse_uri(&uri)
+                .is_ok_and(|(page_workspace, _, _)| page_workspace == workspace);
+            if !in_workspace {
+                continue;
+            }
+            // Replacing the buffer would throw away the user's edits, the page is refreshed once
+            // they're saved instead.
+            if self.has_unsaved_edits(uri.as_str()) {
+                debug!("not refreshing {uri}, it has unsaved edits");
+                continue;
+            }
+
+            // A page that fails to render, e.g. a commit page of an abandoned commit, mustn't keep
+            // the other pages from updating.
+            if let Err(e) = self.refresh_page(&uri, false).await {
+                log::error!("Error refreshing {uri}: {e}");
+            }
+        }
+
+        _ = self.client.semantic_tokens_refresh().await;
+
+        Ok(())
+    }
+
+    /// Re-renders the open page at `uri` and updates the editor's buffer to match. Only the lines
+    /// that changed are replaced, so the cursor and folds stay where they are. With `snapshot`,
+    /// the working copy is snapshotted before rendering.
+    async fn refresh_page(&self, uri: &Url, snapshot: bool) -> anyhow::Result<()> {
+        let Some(page) = self.render_page(uri, snapshot).await? else {
+            return Ok(());
+        };
+        let old_text = self
+            .document_map
+            .insert(uri.to_string(), Rope::from_str(&page.text))
+            .map(|rope| rope.to_string())
+            .unwrap_or_default();
+        let edits = text_edits(&old_text, &page.text)
+            .ok_or_else(|| anyhow!("failed to compute the edits to {uri}"))?;
+        self.page_map.insert(uri.to_string(), page);
+        if edits.is_empty() {
+            return Ok(());
+        }
+
+        let response = self
+            .client
+            .apply_edit(WorkspaceEdit::new(HashMap::from([(uri.clone(), edits)])))
+            .await?;
+        if !response.applied {
+            log::warn!(
+                "Editor did not update {uri}: {}",
+                response.failure_reason.unwrap_or_default()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Whether the editor's buffer of the page at `uri` was edited since the page was last
+    /// rendered into it.
+    fn has_unsaved_edits(&self, uri: &str) -> bool {
+        let (Some(document), Some(page)) = (self.document_map.get(uri), self.page_map.get(uri))
+        else {
+            return false;
+        };
+        *document != page.text
+    }
+
+    /// Asks the user to confirm a destructive change described by `message`, offering `action`
+    /// as the confirming choice.
+    async fn confirm(&self, message: String, action: &str) -> anyhow::Result<bool> {
+        let actions = [action, "Cancel"]
+            .into_iter()
+            .map(|title| MessageActionItem {
+                title: title.to_owned(),
+                properties: Default::default(),
+            })
+            .collect();
+
+        let picked = self
+            .client
+            .show_message_request(MessageType::WARNING, message, Some(actions))
+            .await?;
+
+        Ok(picked.is_some_and(|picked| picked.title == action))
+    }
+
+    /// Asks the user to pick a revision other than `exclude` among the nearby ones, returning its
+    /// change id. With `mutable_only`, only mutable revisions are offered. Clients that can prompt
+    /// for text also offer to enter any revset, which is returned as is.
+    async fn pick_revision(
+        &self,
+        workspace: &Path,
+        exclude: &str,
+        mutable_only: bool,
+    ) -> anyhow::Result<Option<String>> {
+        let choices = self
+            .with_repo(workspace, |repo| {
+                commands::revision_choices(repo, exclude, mutable_only)
+            })
+            .await?;
+        let input_box = self.input_box.load(Ordering::Relaxed);
+        let mut actions: Vec<_> = choices
+            .iter()
+            .map(|(title, _)| MessageActionItem {
+                title: title.clone(),
+                properties: Default::default(),
+            })
+            .collect();
+        if input_box {
+            actions.push(MessageActionItem {
+                title: OTHER_REVISION.to_string(),
+                properties: Default::default(),
+            });
+        }
+
+        let picked = self
+            .client
+            .show_message_request(MessageType::INFO, "Select a revision", Some(actions))
+            .await?;
+        let Some(picked) = picked else {
+            return Ok(None);
+        };
+
+        if input_box && picked.title == OTHER_REVISION {
+            let revset = self
+                .client
+                .send_request::<ShowInputBox>(ShowInputBoxParams {
+                    prompt: "Revset of the revision".to_string(),
+                })
+                .await?;
+            return Ok(revset.filter(|revset| !revset.trim().is_empty()));
+        }
+
+        Ok(choices
+            .into_iter()
+            .find(|(title, _)| *title == picked.title)
+            .map(|(_, change_id)| change_id))
+    }
+
+    /// The repo of the workspace containing `path`. It is loaded on first use, after which it is
+    /// only reloaded when there are new operations, see [`Repo::snapshot`], or when the jj config
+    /// changed.
+    async fn repo(&self, path: &Path) -> anyhow::Result<Arc<Mutex<Repo>>> {
+        let cached = path
+            .ancestors()
+            .find_map(|dir| self.repos.get(dir))
+            .map(|repo| Arc::clone(repo.value()));
+        if let Some(repo) = cached {
+            {
+                let mut locked = repo.lock().await;
+                if locked.config_changed() {
+                    debug!("reloading {} for the changed config", path.display());
+                    *locked = Repo::detect(locked.workspace_dir())?
+                        .ok_or_else(|| anyhow!("no jj root found"))?;
+                }
+            }
+            return Ok(repo);
+        }
+
+        let repo = Repo::detect(path)?.ok_or_else(|| anyhow!("no jj root found"))?;
+        let workspace = repo.workspace_dir().to_owned();
+        let repo = Arc::new(Mutex::new(repo));
+        self.repos.insert(workspace, Arc::clone(&repo));
+
+        Ok(repo)
+    }
+
+    /// Runs `f` with the repo of the workspace containing `path`.
+    ///
+    /// The working copy is snapshotted first, since `f` usually resolves revisions before it
+    /// starts a transaction: without the snapshot, `@` and its change id would still resolve to
+    /// the working-copy commit that the transaction's own snapshot rewrites.
+    async fn with_repo<T>(
+        &self,
+        path: &Path,
+        f: impl FnOnce(&mut Repo) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let repo = self.repo(path).await?;
+        let mut repo = repo.lock().await;
+        repo.snapshot()?;
+        f(&mut repo)
+    }
+
+    /// Renders the page at `uri` on the blocking thread pool, after snapshotting the working copy
+    /// if `snapshot` is set. Starting another render of the same page cancels this one, which then
+    /// returns `None`.
+    async fn render_page(&self, uri: &Url, snapshot: bool) -> anyhow::Result<Option<Page>> {
+        let (repo_path, page, arguments) = pages::path::parse_uri(uri)?;
+        let repo = self.repo(&repo_path).await?;
+
+        let pending = Arc::new(PendingRender {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            finished: watch::Sender::new(false),
+        });
+        if let Some(previous) = self.renders.insert(uri.to_string(), Arc::clone(&pending)) {
+            previous.cancelled.store(true, Ordering::Relaxed);
+        }
+
+        let render = tokio::task::spawn_blocking({
+            let cancelled = Arc::clone(&pending.cancelled);
+            move || {
+                let mut repo = repo.blocking_lock();
+                if cancelled.load(Ordering::Relaxed) {
+                    return Err(anyhow!("rendering was cancelled"));
+                }
+                if snapshot {
+                    repo.snapshot()?;
+                }
+
+                let arguments: Vec<_> = arguments.iter().map(String::as_str).collect();
+                let mut out = PageWriter {
+                    cancelled,
+                    ..Default::default()
+                };
+                page.render(&mut out, &repo, &arguments)?;
+
+                Ok(out.finish())
+            }
+        });
+        let result = self
+            .with_progress(format!("Rendering {}", page.name()), render)
+            .await;
+
+        self.renders
+            .remove_if(uri.as_str(), |_, current| Arc::ptr_eq(current, &pending));
+        pending.finished.send_replace(true);
+        if pending.cancelled.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        result.map(Some)
+    }
+
+    /// Waits until the render of the page at `uri` in progress, if any, is done.
+    async fn wait_for_render(&self, uri: &Url) {
+        let Some(pending) = self
+            .renders
+            .get(uri.as_str())
+            .map(|pending| Arc::clone(pending.value()))
+        else {
+            return;
+        };
+        _ = pending
+            .finished
+            .subscribe()
+            .wait_for(|finished| *finished)
+            .await;
+    }
+
+    /// Awaits `task`, reporting it to the client as `title` with `$/progress` once it takes long
+    /// enough to notice, e.g. when annotating a huge file.
+    async fn with_progress<T>(
+        &self,
+        title: String,
+        mut task: JoinHandle<anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        if let Ok(result) = tokio::time::timeout(PROGRESS_DELAY, &mut task).await {
+            return result?;
+        }
+        if !self.work_done_progress.load(Ordering::Relaxed) {
+            return task.await?;
+        }
+
+        let token = NumberOrString::String(format!(
+            "jjmagit/{}",
+            self.progress_tokens.fetch_add(1, Ordering::Relaxed)
+        ));
+        let created = self
+            .client
+            .send_request::<request::WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: token.clone(),
+            })
+            .await
+            .is_ok();
+        if created {
+            self.client
+                .send_notification::<notification::Progress>(ProgressParams {
+                    token: token.clone(),
+                    value: ProgressParamsValue::WorkDone(WorkDoneProgress::Begin(
+                        WorkDoneProgressBegin {
+                            title,
+                            ..Default::default()
+                        },
+                    )),
+                })
+                .await;
+        }
+
+        let result = task.await;
+
+        if created {
+            self.client
+                .send_notification::<notification::Progress>(ProgressParams {
+                    token,
+                    value: ProgressParamsValue::WorkDone(WorkDoneProgress::End(
+                        WorkDoneProgressEnd::default(),
+                    )),
+                })
+                .await;
+        }
+
+        result?
+    }
 }
 
 #[tokio::main]
@@ -644,15 +1603,124 @@ async fn main() {
 
     let (service, socket) = LspService::build(|client| Backend {
         client,
-        document_map: DashMap::new(),
-        page_map: DashMap::new(),
+        document_map: Default::default(),
+        page_map: Default::default(),
+        repos: Default::default(),
+        renders: Default::default(),
+        work_done_progress: Default::default(),
+        progress_tokens: Default::default(),
+        watch_files: Default::default(),
+        input_box: Default::default(),
+        pending_refreshes: Default::default(),
         workspace_folders: Default::default(),
     })
+    .custom_method("jjmagit/page", Backend::page_text)
     .finish();
 
     Server::new(stdin, stdout, socket).serve(service).await;
 }
 
+/// Whether changes to `paths` can change the pages of `repo`'s workspace: a new operation, or an
+/// edit to a working-copy file that snapshots don't ignore. Other changes inside `.jj` are
+/// bookkeeping, e.g. by a snapshot, and must not trigger another refresh.
+fn is_repo_change(repo: &Repo, paths: &[PathBuf]) -> anyhow::Result<bool> {
+    let mut files = Vec::new();
+    for path in paths {
+        let Ok(relative) = path.strip_prefix(repo.workspace_dir()) else {
+            continue;
+        };
+        if relative.starts_with(".jj") {
+            if relative.starts_with(".jj/repo/op_heads") {
+                return Ok(true);
+            }
+            continue;
+        }
+        if !relative
+            .components()
+            .any(|component| component.as_os_str() == ".jj" || component.as_os_str() == ".git")
+        {
+            files.push(relative);
+        }
+    }
+
+    Ok(!files.is_empty() && !repo.ignores_all(&files)?)
+}
+
+/// Diagnostics for `errors` in the lines of `text`, an editable region starting at byte `offset`
+/// of `rope`.
+fn line_diagnostics(
+    rope: &Rope,
+    text: &str,
+    offset: usize,
+    errors: &[(usize, String)],
+) -> Vec<Diagnostic> {
+    let mut line_starts = vec![offset];
+    line_starts.extend(
+        text.match_indices('\n')
+            .map(|(newline, _)| offset + newline + 1),
+    );
+
+    errors
+        .iter()
+        .filter_map(|(line, message)| {
+            let start = *line_starts.get(*line)?;
+            let end = start
+                + text[start - offset..]
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .len();
+            Some(Diagnostic {
+                range: Range::new(
+                    offset_to_position(rope.try_byte_to_char(start).ok()?, rope)?,
+                    offset_to_position(rope.try_byte_to_char(end).ok()?, rope)?,
+                ),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("jjmagit".to_owned()),
+                message: message.clone(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// The edits that turn `old` into `new`, replacing only the lines that differ. Positions are in
+/// UTF-16 code units, the LSP default. Returns `None` if a hunk has no position in `old`, since
+/// applying only some of the edits would garble the buffer.
+fn text_edits(old: &str, new: &str) -> Option<Vec<TextEdit>> {
+    let rope = Rope::from_str(old);
+    let byte_to_position = |offset: usize| {
+        let line = rope.try_byte_to_line(offset).ok()?;
+        let line_start = rope
+            .try_char_to_utf16_cu(rope.try_line_to_char(line).ok()?)
+            .ok()?;
+        let utf16_offset = rope
+            .try_char_to_utf16_cu(rope.try_byte_to_char(offset).ok()?)
+            .ok()?;
+        Some(Position::new(
+            line as u32,
+            (utf16_offset - line_start) as u32,
+        ))
+    };
+
+    ContentDiff::by_line([old, new])
+        .hunk_ranges()
+        .filter(|hunk| hunk.kind == DiffHunkKind::Different)
+        .map(|hunk| {
+            let [old_range, new_range] = hunk.ranges.as_slice() else {
+                return None;
+            };
+            Some(TextEdit::new(
+                Range::new(
+                    byte_to_position(old_range.start)?,
+                    byte_to_position(old_range.end)?,
+                ),
+                new[new_range.clone()].to_owned(),
+            ))
+        })
+        .collect()
+}
+
 fn offset_to_position(offset: usize, rope: &Rope) -> Option<Position> {
     let line = rope.try_char_to_line(offset).ok()?;
     let first_char_of_line = rope.try_line_to_char(line).ok()?;
@@ -674,6 +1742,37 @@ fn intersects(range1: &std::ops::Range<usize>, range2: &std::ops::Range<usize>)
     range1.start <= range2.end && range2.start <= range1.end
 }
 
+fn workspace_and_change_id(command: &ExecuteCommandParams) -> anyhow::Result<(&Path, &str)> {
+    let [workspace, change_id] = command.arguments.as_slice() else {
+        return Err(anyhow!(
+            "wrong arguments to command {}: {:?}",
+            command.command,
+            command.arguments
+        ));
+    };
+    let workspace = workspace
+        .as_str()
+        .map(Path::new)
+        .ok_or_else(|| anyhow!("wrong parameter workspace: {:?}", workspace))?;
+    let change_id = change_id
+        .as_str()
+        .ok_or_else(|| anyhow!("wrong parameter change_id: {:?}", change_id))?;
+
+    Ok((workspace, change_id))
+}
+
+fn str_arguments(command: &ExecuteCommandParams) -> anyhow::Result<Vec<&str>> {
+    command
+        .arguments
+        .iter()
+        .map(|argument| {
+            argument
+                .as_str()
+                .ok_or_else(|| anyhow!("wrong parameter to {}: {:?}", command.command, argument))
+        })
+        .collect()
+}
+
 fn value_as_option(val: &Value) -> Option<&Value> {
     match val {
         Value::Null => None,
@@ -1,13 +1,41 @@
+use anyhow::{Result, ensure};
 use jj_cli::formatter::{Formatter, PlainTextFormatter};
 use jj_lib::commit::Commit;
+use jj_lib::copies::CopiesTreeDiffEntryPath;
+use jj_lib::operation::Operation;
+use jj_lib::repo_path::{RepoPath, RepoPathBuf};
 use tower_lsp::lsp_types::Url;
 
+use crate::jj::{DiffHunk, Repo};
 use crate::span::Span;
-use crate::{commands, semantic_token};
+use crate::{commands, pages, semantic_token};
 use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 type TokenType = u32;
 
+/// The tokens of the sides of a conflict, reused for conflicts with more sides.
+const CONFLICT_SIDE_TOKENS: [&str; 3] = ["conflict_side_1", "conflict_side_2", "conflict_side_3"];
+
+#[derive(Clone, Copy)]
+enum ConflictSection {
+    Base,
+    Side(usize),
+}
+
+/// The marker character of `line`, if it is a conflict marker: a character repeated at least 7
+/// times, followed by a space or the end of the line.
+fn conflict_marker(line: &str) -> Option<u8> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let marker = *line.as_bytes().first()?;
+    let len = line.bytes().take_while(|&c| c == marker).count();
+    let rest = &line[len..];
+    (b"<>|=%\\+-".contains(&marker) && len >= 7 && (rest.is_empty() || rest.starts_with(' ')))
+        .then_some(marker)
+}
+
 #[derive(Debug)]
 pub struct Page {
     pub text: String,
@@ -15,6 +43,160 @@ pub struct Page {
     pub folding_ranges: Vec<(Span, ())>,
     pub goto_def: Vec<(Span, GotoDefinitionTarget)>,
     pub code_actions: Vec<(Span, CodeAction)>,
+    pub diff_lines: Vec<(Span, DiffLine)>,
+    pub changed_files: Vec<(Span, ChangedFile)>,
+    pub editable: Vec<(Span, Editable)>,
+}
+
+/// A region of a page that the user may edit; changes to it are applied when the page is saved.
+#[derive(Debug, Clone)]
+pub enum Editable {
+    Description {
+        change_id: String,
+    },
+    /// Lines of the form `<name> [<revision>]`.
+    NewBookmarks,
+    /// Lines of the form `<action> <change> [<subject>]` for the linear chain `change_ids`,
+    /// oldest first.
+    RebasePlan {
+        change_ids: Vec<String>,
+    },
+}
+
+impl Page {
+    /// Code actions for the changed files or diff lines touched by `selection`: several files of
+    /// the same commit, or lines of the same file.
+    pub fn line_actions(&self, workspace: &Path, selection: &Span) -> Vec<CodeAction> {
+        let files = self
+            .changed_files
+            .iter()
+            .filter(|(span, _)| span.start < selection.end && selection.start < span.end)
+            .map(|(_, file)| file)
+            .collect::<Vec<_>>();
+        if let [first, rest @ ..] = files.as_slice()
+            && !rest.is_empty()
+        {
+            if rest
+                .iter()
+                .any(|file| file.commit.id() != first.commit.id())
+            {
+                return Vec::new();
+            }
+            let paths = files
+                .iter()
+                .flat_map(|file| file.paths.iter().cloned())
+                .collect::<Vec<_>>();
+            return CodeAction::split_files(workspace, &first.commit, &paths, "selected files");
+        }
+
+        let mut lines = self
+            .diff_lines
+            .iter()
+            .enumerate()
+            .filter(|(_, (span, line))| {
+                span.start < selection.end && selection.start < span.end && line.is_change()
+            });
+
+        let Some((first_index, (_, first))) = lines.next() else {
+            return Vec::new();
+        };
+        let mut last_index = first_index;
+        for (index, (_, line)) in lines {
+            if line.path != first.path || line.commit.id() != first.commit.id() {
+                return Vec::new();
+            }
+            last_index = index;
+        }
+
+        // The checksum of the selection covers all of its lines, so they must have been rendered:
+        // the selection can't span the unchanged lines left out between two hunks.
+        let selected = &self.diff_lines[first_index..=last_index];
+        let contiguous = selected.windows(2).all(|pair| {
+            let (previous, next) = (&pair[0].1.hunk, &pair[1].1.hunk);
+            previous.before.end == next.before.start && previous.after.end == next.after.start
+        });
+        if !contiguous {
+            return Vec::new();
+        }
+
+        let last = &selected[selected.len() - 1].1;
+        let hunk = DiffHunk {
+            before: first.hunk.before.start..last.hunk.before.end,
+            after: first.hunk.after.start..last.hunk.after.end,
+        };
+        CodeAction::hunk_actions(
+            workspace,
+            &first.commit,
+            &first.path,
+            &hunk,
+            &diff_lines_checksum(&self.text, selected),
+            "selected lines",
+        )
+    }
+
+    /// Extracts the contents of the editable regions that differ in `text`, an edited version of
+    /// this page, along with their byte offsets in `text`. Fails if anything outside of the
+    /// editable regions was changed.
+    pub fn edits<'a>(&self, text: &'a str) -> Result<Vec<(&Editable, usize, &'a str)>> {
+        if self.editable.is_empty() {
+            ensure!(text == self.text, "This page is read-only");
+            return Ok(Vec::new());
+        }
+
+        let mut edits = Vec::new();
+        ensure!(
+            self.match_editables(text, 0, 0, &mut edits),
+            "Only the editable parts of the page may be changed"
+        );
+
+        Ok(edits
+            .into_iter()
+            .zip(&self.editable)
+            .filter(|((_, _, edited), (span, _))| *edited != &self.text[span.clone()])
+            .map(|(edit, _)| edit)
+            .collect())
+    }
+
+    /// Matches the edited page `text` from `offset` on against this page from `read_only_start`
+    /// on, adding the contents of the editable regions that follow the ones in `edits`. An edited
+    /// region may contain the read-only text after it, e.g. a description quoting a heading, so
+    /// every place where that text occurs is tried until the rest of the page matches.
+    fn match_editables<'p, 'a>(
+        &'p self,
+        text: &'a str,
+        offset: usize,
+        read_only_start: usize,
+        edits: &mut Vec<(&'p Editable, usize, &'a str)>,
+    ) -> bool {
+        let Some((span, editable)) = self.editable.get(edits.len()) else {
+            return text[offset..] == self.text[read_only_start..];
+        };
+        let read_only = &self.text[read_only_start..span.start];
+        if !text[offset..].starts_with(read_only) {
+            return false;
+        }
+        let start = offset + read_only.len();
+        let rest = &text[start..];
+
+        let following = match self.editable.get(edits.len() + 1) {
+            Some((next, _)) => &self.text[span.end..next.start],
+            None => &self.text[span.end..],
+        };
+        let ends = rest
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([rest.len()])
+            .filter(|&end| rest[end..].starts_with(following));
+        for end in ends {
+            edits.push((editable, start, &rest[..end]));
+            if self.match_editables(text, start + end, span.end, edits) {
+                return true;
+            }
+            edits.pop();
+        }
+
+        false
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -24,11 +206,70 @@ pub struct CodeAction {
     pub args: Vec<String>,
 }
 
+/// Provenance of a line of a rendered diff.
+#[derive(Debug, Clone)]
+pub struct DiffLine {
+    pub commit: Commit,
+    pub path: RepoPathBuf,
+    /// The line as a hunk of its own: context lines cover one line on both sides, removed and
+    /// added lines only on one side.
+    pub hunk: DiffHunk,
+}
+
+/// A file of a rendered list of changes, with the paths it covers: both the source and the
+/// target, for copies and renames.
+#[derive(Debug, Clone)]
+pub struct ChangedFile {
+    pub commit: Commit,
+    pub paths: Vec<RepoPathBuf>,
+}
+
+impl DiffLine {
+    pub fn is_change(&self) -> bool {
+        self.hunk.before.is_empty() || self.hunk.after.is_empty()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct GotoDefinitionTarget {
     pub target: Url,
 }
 
+impl GotoDefinitionTarget {
+    pub fn page(workspace: &Path, page: &dyn pages::Page, arguments: &[&str]) -> Self {
+        GotoDefinitionTarget {
+            target: pages::path::get_uri(workspace, page, arguments),
+        }
+    }
+
+    pub fn commit(repo: &Repo, commit: &Commit) -> Self {
+        let change_id = commit.change_id().to_string();
+        Self::page(repo.workspace_dir(), &pages::Commit, &[&change_id])
+    }
+
+    pub fn operation(repo: &Repo, operation: &Operation) -> Self {
+        let op_id = operation.id().to_string();
+        Self::page(repo.workspace_dir(), &pages::Operation, &[&op_id])
+    }
+
+    pub fn evolog(repo: &Repo, commit: &Commit) -> Self {
+        let change_id = commit.change_id().to_string();
+        Self::page(repo.workspace_dir(), &pages::Evolog, &[&change_id])
+    }
+
+    /// The file at `path` in the working copy.
+    pub fn file(repo: &Repo, path: &RepoPath) -> Self {
+        GotoDefinitionTarget {
+            target: Url::from_file_path(path.to_fs_path_unchecked(repo.workspace_dir())).unwrap(),
+        }
+    }
+
+    pub fn describe(repo: &Repo, commit: &Commit) -> Self {
+        let change_id = commit.change_id().to_string();
+        Self::page(repo.workspace_dir(), &pages::Describe, &[&change_id])
+    }
+}
+
 #[derive(Default)]
 pub struct PageWriter {
     pub buf: String,
@@ -37,8 +278,14 @@ pub struct PageWriter {
     pub folds: WriterStack<()>,
     pub goto_def: WriterStack<GotoDefinitionTarget>,
     pub code_actions: WriterStack<Vec<CodeAction>>,
+    pub diff_lines: Vec<(Span, DiffLine)>,
+    pub changed_files: Vec<(Span, ChangedFile)>,
+    pub editable: Vec<(Span, Editable)>,
 
     pub debug: bool,
+    /// Set when the page is no longer needed, e.g. because a newer render of it started. Writes
+    /// fail from then on, so rendering stops early.
+    pub cancelled: Arc<AtomicBool>,
 }
 
 pub struct WriterStack<T, const DISJOINT: bool = false> {
@@ -61,6 +308,9 @@ impl PageWriter {
                 .into_iter()
                 .flat_map(|(range, item)| item.into_iter().map(move |item| (range.clone(), item)))
                 .collect(),
+            diff_lines: self.diff_lines,
+            changed_files: self.changed_files,
+            editable: self.editable,
         }
     }
 
@@ -89,6 +339,72 @@ impl PageWriter {
         }
     }
 
+    /// Writes `text` as an editable region.
+    pub fn write_editable(&mut self, editable: Editable, text: &str) {
+        let start = self.buf.len();
+        self.buf.push_str(text);
+        self.editable.push((start..self.buf.len(), editable));
+    }
+
+    /// Appends `row`, a graph row drawn around the text of `content`, keeping the labels, links and
+    /// code actions of `content` on the lines of `row` that end with the corresponding text.
+    pub fn write_graph_row(&mut self, row: &str, content: PageWriter) {
+        let mut shifts = Vec::new();
+        let mut content_start = 0;
+        let mut content_lines = content.buf.split_inclusive('\n');
+        for row_line in row.split_inclusive('\n') {
+            let content_line = content_lines.next().unwrap_or_default();
+            let row_text = row_line.trim_end_matches('\n');
+            let content_text = content_line.trim_end_matches('\n');
+            let prefix = row_text
+                .strip_suffix(content_text)
+                .or_else(|| row_text.strip_suffix(content_text.trim_end()));
+            if let Some(prefix) = prefix {
+                let shift = (self.buf.len() + prefix.len()) as isize - content_start as isize;
+                shifts.push((content_start..content_start + content_line.len(), shift));
+            }
+            content_start += content_line.len();
+            self.buf.push_str(row_line);
+        }
+
+        let shift_of = |offset: usize| {
+            shifts
+                .iter()
+                .find(|(line, _)| line.contains(&offset))
+                .map(|(_, shift)| *shift)
+        };
+        let shift_span = |span: Span| -> Option<Span> {
+            let start = span.start.checked_add_signed(shift_of(span.start)?)?;
+            let end = match span.end.checked_sub(1) {
+                Some(last) if !span.is_empty() => span.end.checked_add_signed(shift_of(last)?)?,
+                _ => start,
+            };
+            Some(start..end)
+        };
+        fn extend<T>(
+            target: &mut Vec<(Span, T)>,
+            source: Vec<(Span, T)>,
+            shift_span: impl Fn(Span) -> Option<Span>,
+        ) {
+            target.extend(
+                source
+                    .into_iter()
+                    .filter_map(|(span, data)| Some((shift_span(span)?, data))),
+            );
+        }
+        extend(&mut self.labels.done, content.labels.done, shift_span);
+        extend(&mut self.folds.done, content.folds.done, shift_span);
+        extend(&mut self.goto_def.done, content.goto_def.done, shift_span);
+        extend(
+            &mut self.code_actions.done,
+            content.code_actions.done,
+            shift_span,
+        );
+        extend(&mut self.diff_lines, content.diff_lines, shift_span);
+        extend(&mut self.changed_files, content.changed_files, shift_span);
+        extend(&mut self.editable, content.editable, shift_span);
+    }
+
     pub fn push_fold(&mut self) {
         self.folds.push(&self.buf, ());
     }
@@ -106,6 +422,142 @@ impl PageWriter {
         self.code_actions.pop(&self.buf);
     }
 
+    /// Records a span for every hunk of the git diff of `path` in `commit` written since `start`,
+    /// attaching the code actions returned by `actions`, and the provenance of every diff line.
+    pub fn track_hunks(
+        &mut self,
+        start: usize,
+        commit: &Commit,
+        path: &RepoPath,
+        mut actions: impl FnMut(&DiffHunk, &str) -> Vec<CodeAction>,
+    ) {
+        let mut hunks = Vec::new();
+        // The start of the current hunk, and the index of its first line in `diff_lines`.
+        let mut current: Option<(usize, usize, DiffHunk)> = None;
+        let (mut before, mut after) = (0, 0);
+        let mut offset = start;
+        let mut line_end = start;
+        for line in self.buf[start..].split_inclusive('\n') {
+            let line_start = offset;
+            let line_span = line_start..line_start + line.trim_end_matches('\n').len();
+            offset += line.len();
+
+            if line.starts_with("@@") {
+                hunks.extend(current.take().map(|(hunk_start, first_line, hunk)| {
+                    (
+                        hunk_start..line_end,
+                        first_line..self.diff_lines.len(),
+                        hunk,
+                    )
+                }));
+                current =
+                    DiffHunk::parse(line).map(|hunk| (line_start, self.diff_lines.len(), hunk));
+                if let Some((_, _, hunk)) = &current {
+                    (before, after) = (hunk.before.start, hunk.after.start);
+                }
+            } else if current.is_some()
+                && let Some((removed, added)) = match line.as_bytes().first() {
+                    Some(b' ') => Some((1, 1)),
+                    Some(b'-') => Some((1, 0)),
+                    Some(b'+') => Some((0, 1)),
+                    _ => None,
+                }
+            {
+                let hunk = DiffHunk {
+                    before: before..before + removed,
+                    after: after..after + added,
+                };
+                (before, after) = (hunk.before.end, hunk.after.end);
+                self.diff_lines.push((
+                    line_span.clone(),
+                    DiffLine {
+                        commit: commit.clone(),
+                        path: path.to_owned(),
+                        hunk,
+                    },
+                ));
+            }
+            line_end = line_span.end;
+        }
+        hunks.extend(current.map(|(hunk_start, first_line, hunk)| {
+            (
+                hunk_start..line_end,
+                first_line..self.diff_lines.len(),
+                hunk,
+            )
+        }));
+
+        for (span, lines, hunk) in hunks {
+            let checksum = diff_lines_checksum(&self.buf, &self.diff_lines[lines]);
+            self.code_actions
+                .done
+                .push((span, actions(&hunk, &checksum)));
+        }
+    }
+
+    /// Relabels the lines of the diff written since `start` that are part of a materialized
+    /// conflict by the conflict section they belong to: a marker, the base or one of the sides.
+    pub fn label_conflict_sides(&mut self, start: usize) {
+        let mut section = None;
+        let mut section_is_empty = true;
+        let mut next_side = 0;
+        let mut relabelled = Vec::new();
+        let mut offset = start;
+        for line in self.buf[start..].split_inclusive('\n') {
+            let line_span = offset..offset + line.trim_end_matches('\n').len();
+            offset += line.len();
+
+            // Conflicts are part of the new contents, so removed lines can't belong to one.
+            let Some(content) = line.strip_prefix(['+', ' ']) else {
+                continue;
+            };
+            let token = match (conflict_marker(content), section) {
+                (Some(b'<'), _) => {
+                    (section, section_is_empty, next_side) =
+                        (Some(ConflictSection::Side(0)), true, 1);
+                    "conflict_marker"
+                }
+                (Some(b'%' | b'+' | b'='), Some(current)) => {
+                    // A side directly following the start marker is the first side.
+                    if !(section_is_empty && matches!(current, ConflictSection::Side(_))) {
+                        section = Some(ConflictSection::Side(next_side));
+                        next_side += 1;
+                    }
+                    section_is_empty = true;
+                    "conflict_marker"
+                }
+                (Some(b'|' | b'-'), Some(_)) => {
+                    (section, section_is_empty) = (Some(ConflictSection::Base), true);
+                    "conflict_marker"
+                }
+                (Some(b'\\'), Some(_)) => "conflict_marker",
+                (Some(b'>'), Some(_)) => {
+                    section = None;
+                    "conflict_marker"
+                }
+                (_, Some(ConflictSection::Base)) => {
+                    section_is_empty = false;
+                    "conflict_base"
+                }
+                (_, Some(ConflictSection::Side(side))) => {
+                    section_is_empty = false;
+                    CONFLICT_SIDE_TOKENS[side % CONFLICT_SIDE_TOKENS.len()]
+                }
+                (_, None) => continue,
+            };
+            relabelled.push((line_span, semantic_token::get(token)));
+        }
+
+        if relabelled.is_empty() {
+            return;
+        }
+        // `relabel` looks up the labels by their start, they're almost sorted already.
+        self.labels.done.sort_by_key(|(range, _)| range.start);
+        for (span, token) in relabelled {
+            self.labels.relabel(span, token);
+        }
+    }
+
     pub fn plaintext(&mut self) -> impl Formatter + '_ {
         PlainTextFormatter::new(&mut *self)
     }
@@ -176,6 +628,9 @@ impl Formatter for FormatterAdapter<'_> {
 
 impl std::io::Write for PageWriter {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(std::io::Error::other("rendering was cancelled"));
+        }
         let str = std::str::from_utf8(buf).map_err(std::io::Error::other)?;
         self.buf.push_str(str);
 
@@ -218,6 +673,41 @@ impl<T: Clone, const DISJOINT: bool> WriterStack<T, DISJOINT> {
     }
 }
 
+impl<T: Clone> WriterStack<T, true> {
+    /// Labels `span` with `data` instead of the labels already written there. The labels must be
+    /// sorted by their start, and stay sorted.
+    pub fn relabel(&mut self, span: Span, data: T) {
+        let mut start = self
+            .done
+            .partition_point(|(range, _)| range.start < span.start);
+        // Labels don't overlap, so only the one before can reach into `span`.
+        if start > 0 && self.done[start - 1].0.end > span.start {
+            start -= 1;
+        }
+        let end = self
+            .done
+            .partition_point(|(range, _)| range.start < span.end);
+
+        let mut labels = Vec::with_capacity(end - start + 2);
+        let mut after = Vec::new();
+        for (range, item) in &self.done[start..end] {
+            if range.end <= span.start || span.end <= range.start {
+                after.push((range.clone(), item.clone()));
+                continue;
+            }
+            if range.start < span.start {
+                labels.push((range.start..span.start, item.clone()));
+            }
+            if span.end < range.end {
+                after.push((span.end..range.end, item.clone()));
+            }
+        }
+        labels.push((span, data));
+        labels.append(&mut after);
+        self.done.splice(start..end, labels);
+    }
+}
+
 pub struct ScopedWriter<'a, T, const DISJOINT: bool = false> {
     stack: &'a mut WriterStack<T, DISJOINT>,
     buf: &'a mut String,
@@ -239,35 +729,395 @@ impl<T: Clone, const DISJOINT: bool> ScopedWriter<'_, T, DISJOINT> {
 }
 
 impl CodeAction {
-    pub fn move_to_commit() -> Self {
+    pub fn move_to_commit(workspace: &Path, commit: &Commit) -> Self {
         CodeAction {
-            title: "Move to first commit".into(),
-            command: "todo",
-            args: vec![],
+            title: "Move changes to parent commit".into(),
+            command: commands::SQUASH,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                commit.change_id().to_string(),
+                format!("{}-", commit.change_id()),
+            ],
         }
     }
 
-    pub fn move_file_to_commit(pretty_path: String) -> Self {
+    pub fn move_file_to_commit(
+        workspace: &Path,
+        commit: &Commit,
+        pretty_path: &str,
+        path: &CopiesTreeDiffEntryPath,
+    ) -> Self {
+        let mut args = vec![
+            workspace.to_string_lossy().into_owned(),
+            commit.change_id().to_string(),
+            format!("{}-", commit.change_id()),
+        ];
+        args.extend(
+            [path.source(), path.target()]
+                .into_iter()
+                .map(|path| path.as_internal_file_string().to_owned()),
+        );
+        args.dedup();
+
         CodeAction {
-            title: format!("Move {} to first commit", pretty_path),
-            command: commands::TODO,
-            args: vec![pretty_path],
+            title: format!("Move {pretty_path} to parent commit"),
+            command: commands::SQUASH,
+            args,
         }
     }
 
-    pub fn abandon(commit: &Commit) -> Self {
+    pub fn abandon(workspace: &Path, commit: &Commit) -> Self {
         CodeAction {
             title: format!("Abandon commit {}", commit.change_id()),
-            command: "todo",
-            args: vec![commit.change_id().to_string()],
+            command: commands::ABANDON,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                commit.change_id().to_string(),
+            ],
         }
     }
 
-    pub fn new(commit: &Commit) -> Self {
+    pub fn new(workspace: &Path, commit: &Commit) -> Self {
         CodeAction {
             title: format!("Create new commit at {}", commit.change_id()),
-            command: "todo",
-            args: vec![commit.change_id().to_string()],
+            command: commands::NEW,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                commit.change_id().to_string(),
+            ],
+        }
+    }
+
+    pub fn edit(workspace: &Path, commit: &Commit) -> Self {
+        CodeAction {
+            title: format!("Edit commit {}", commit.change_id()),
+            command: commands::EDIT,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                commit.change_id().to_string(),
+            ],
+        }
+    }
+
+    pub fn duplicate(workspace: &Path, commit: &Commit) -> Self {
+        CodeAction {
+            title: format!("Duplicate commit {}", commit.change_id()),
+            command: commands::DUPLICATE,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                commit.change_id().to_string(),
+            ],
+        }
+    }
+
+    /// The code actions for moving the working copy to the next or previous commit, like
+    /// `jj next` and `jj prev`.
+    pub fn movement_actions(workspace: &Path) -> Vec<Self> {
+        [
+            (commands::NEXT, "Move to next commit"),
+            (commands::PREV, "Move to previous commit"),
+        ]
+        .into_iter()
+        .map(|(command, title)| CodeAction {
+            title: title.to_owned(),
+            command,
+            args: vec![workspace.to_string_lossy().into_owned()],
+        })
+        .collect()
+    }
+
+    /// The code actions for moving, splitting or discarding `hunk`, described as `subject`.
+    pub fn hunk_actions(
+        workspace: &Path,
+        commit: &Commit,
+        path: &RepoPath,
+        hunk: &DiffHunk,
+        checksum: &str,
+        subject: &str,
+    ) -> Vec<Self> {
+        vec![
+            Self::move_hunk_to_commit(workspace, commit, path, hunk, checksum, subject),
+            Self::move_hunk_to_revision(workspace, commit, path, hunk, checksum, subject),
+            Self::split_hunk(workspace, commit, path, hunk, checksum, subject),
+            Self::restore_hunk(workspace, commit, path, hunk, checksum, subject),
+        ]
+    }
+
+    pub fn move_hunk_to_commit(
+        workspace: &Path,
+        commit: &Commit,
+        path: &RepoPath,
+        hunk: &DiffHunk,
+        checksum: &str,
+        subject: &str,
+    ) -> Self {
+        CodeAction {
+            title: format!("Move {subject} to parent commit"),
+            command: commands::SQUASH_HUNK,
+            args: hunk_args(
+                workspace,
+                commit,
+                Some(format!("{}-", commit.change_id())),
+                path,
+                hunk,
+                checksum,
+            ),
+        }
+    }
+
+    pub fn move_hunk_to_revision(
+        workspace: &Path,
+        commit: &Commit,
+        path: &RepoPath,
+        hunk: &DiffHunk,
+        checksum: &str,
+        subject: &str,
+    ) -> Self {
+        CodeAction {
+            title: format!("Move {subject} to another revision..."),
+            command: commands::SQUASH_HUNK,
+            args: hunk_args(workspace, commit, Some(String::new()), path, hunk, checksum),
+        }
+    }
+
+    pub fn split_hunk(
+        workspace: &Path,
+        commit: &Commit,
+        path: &RepoPath,
+        hunk: &DiffHunk,
+        checksum: &str,
+        subject: &str,
+    ) -> Self {
+        CodeAction {
+            title: format!("Split {subject} into a new commit"),
+            command: commands::SPLIT_HUNK,
+            args: hunk_args(workspace, commit, None, path, hunk, checksum),
+        }
+    }
+
+    pub fn restore_hunk(
+        workspace: &Path,
+        commit: &Commit,
+        path: &RepoPath,
+        hunk: &DiffHunk,
+        checksum: &str,
+        subject: &str,
+    ) -> Self {
+        CodeAction {
+            title: format!("Discard {subject}"),
+            command: commands::RESTORE_HUNK,
+            args: hunk_args(workspace, commit, None, path, hunk, checksum),
+        }
+    }
+
+    /// The code actions for rebasing `commit` to a revision picked by the user.
+    pub fn rebase_actions(workspace: &Path, commit: &Commit) -> Vec<Self> {
+        let change_id = commit.change_id();
+        [
+            ("revision", format!("Rebase {change_id} onto...")),
+            ("branch", format!("Rebase branch of {change_id} onto...")),
+            ("after", format!("Insert {change_id} after...")),
+            ("before", format!("Insert {change_id} before...")),
+        ]
+        .into_iter()
+        .map(|(mode, title)| CodeAction {
+            title,
+            command: commands::REBASE,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                change_id.to_string(),
+                mode.to_owned(),
+                String::new(),
+            ],
+        })
+        .collect()
+    }
+
+    pub fn restore_files(
+        workspace: &Path,
+        commit: &Commit,
+        paths: &[RepoPathBuf],
+        pretty_path: &str,
+    ) -> Self {
+        let mut args = vec![
+            workspace.to_string_lossy().into_owned(),
+            commit.change_id().to_string(),
+        ];
+        args.extend(
+            paths
+                .iter()
+                .map(|path| path.as_internal_file_string().to_owned()),
+        );
+        CodeAction {
+            title: format!("Restore {pretty_path} from parent"),
+            command: commands::RESTORE,
+            args,
+        }
+    }
+
+    /// The code actions for splitting the changes to `paths`, described as `subject`, into a new
+    /// commit before or after `commit`.
+    pub fn split_files(
+        workspace: &Path,
+        commit: &Commit,
+        paths: &[RepoPathBuf],
+        subject: &str,
+    ) -> Vec<Self> {
+        ["before", "after"]
+            .into_iter()
+            .map(|position| {
+                let mut args = vec![
+                    workspace.to_string_lossy().into_owned(),
+                    commit.change_id().to_string(),
+                    position.to_owned(),
+                ];
+                args.extend(
+                    paths
+                        .iter()
+                        .map(|path| path.as_internal_file_string().to_owned()),
+                );
+                CodeAction {
+                    title: format!("Split {subject} into a new commit {position}"),
+                    command: commands::SPLIT,
+                    args,
+                }
+            })
+            .collect()
+    }
+
+    pub fn create_bookmark(workspace: &Path, commit: &Commit) -> Self {
+        CodeAction {
+            title: format!("Create bookmark at {}", commit.change_id()),
+            command: commands::CREATE_BOOKMARK,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                commit.change_id().to_string(),
+            ],
+        }
+    }
+
+    pub fn move_bookmark(workspace: &Path, name: &str, revision: &str) -> Self {
+        CodeAction {
+            title: format!("Move bookmark {name} to {revision}"),
+            command: commands::MOVE_BOOKMARK,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                name.to_owned(),
+                revision.to_owned(),
+            ],
+        }
+    }
+
+    pub fn delete_bookmark(workspace: &Path, name: &str) -> Self {
+        CodeAction {
+            title: format!("Delete bookmark {name}"),
+            command: commands::DELETE_BOOKMARK,
+            args: vec![workspace.to_string_lossy().into_owned(), name.to_owned()],
+        }
+    }
+
+    pub fn forget_bookmark(workspace: &Path, name: &str) -> Self {
+        CodeAction {
+            title: format!("Forget bookmark {name}"),
+            command: commands::FORGET_BOOKMARK,
+            args: vec![workspace.to_string_lossy().into_owned(), name.to_owned()],
+        }
+    }
+
+    pub fn track_bookmark(workspace: &Path, name: &str, remote: &str) -> Self {
+        CodeAction {
+            title: format!("Track bookmark {name}@{remote}"),
+            command: commands::TRACK_BOOKMARK,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                name.to_owned(),
+                remote.to_owned(),
+            ],
+        }
+    }
+
+    pub fn untrack_bookmark(workspace: &Path, name: &str, remote: &str) -> Self {
+        CodeAction {
+            title: format!("Untrack bookmark {name}@{remote}"),
+            command: commands::UNTRACK_BOOKMARK,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                name.to_owned(),
+                remote.to_owned(),
+            ],
+        }
+    }
+
+    pub fn undo_operation(workspace: &Path, operation: &Operation) -> Self {
+        CodeAction {
+            title: format!("Undo operation {:.12}", operation.id().to_string()),
+            command: commands::UNDO_OPERATION,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                operation.id().to_string(),
+            ],
+        }
+    }
+
+    pub fn restore_operation(workspace: &Path, operation: &Operation) -> Self {
+        CodeAction {
+            title: format!(
+                "Restore repo to operation {:.12}",
+                operation.id().to_string()
+            ),
+            command: commands::RESTORE_OPERATION,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                operation.id().to_string(),
+            ],
+        }
+    }
+
+    /// Resolves the conflict at `path` by taking the `side`th side, described by `label` if the
+    /// conflict has labels.
+    pub fn resolve_conflict(
+        workspace: &Path,
+        commit: &Commit,
+        path: &RepoPath,
+        pretty_path: &str,
+        side: usize,
+        label: Option<&str>,
+    ) -> Self {
+        let title = match label {
+            Some(label) => format!(
+                "Resolve {pretty_path} by taking side {} ({label})",
+                side + 1
+            ),
+            None => format!("Resolve {pretty_path} by taking side {}", side + 1),
+        };
+        CodeAction {
+            title,
+            command: commands::RESOLVE_CONFLICT,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                commit.change_id().to_string(),
+                path.as_internal_file_string().to_owned(),
+                side.to_string(),
+            ],
+        }
+    }
+
+    pub fn restore_file(
+        workspace: &Path,
+        commit: &Commit,
+        source: &Commit,
+        path: &RepoPath,
+        pretty_path: &str,
+    ) -> Self {
+        CodeAction {
+            title: format!("Restore {pretty_path} from {}", source.change_id()),
+            command: commands::RESTORE_FILE,
+            args: vec![
+                workspace.to_string_lossy().into_owned(),
+                commit.change_id().to_string(),
+                source.change_id().to_string(),
+                path.as_internal_file_string().to_owned(),
+            ],
         }
     }
 
@@ -279,3 +1129,92 @@ impl CodeAction {
         }
     }
 }
+
+fn hunk_args(
+    workspace: &Path,
+    commit: &Commit,
+    destination: Option<String>,
+    path: &RepoPath,
+    hunk: &DiffHunk,
+    checksum: &str,
+) -> Vec<String> {
+    let mut args = vec![
+        workspace.to_string_lossy().into_owned(),
+        commit.change_id().to_string(),
+    ];
+    args.extend(destination);
+    args.push(path.as_internal_file_string().to_owned());
+    args.push(hunk.to_string());
+    args.push(checksum.to_owned());
+    args
+}
+
+/// The checksum of the rendered diff `lines` of `text`, see [`DiffHunk::checksum`].
+fn diff_lines_checksum(text: &str, lines: &[(Span, DiffLine)]) -> String {
+    // The lines are rendered with their `-`, `+` or ` ` prefix.
+    let content = |(span, _): &(Span, DiffLine)| &text[span.start + 1..span.end];
+    DiffHunk::checksum(
+        lines
+            .iter()
+            .filter(|(_, line)| !line.hunk.before.is_empty())
+            .map(content),
+        lines
+            .iter()
+            .filter(|(_, line)| !line.hunk.after.is_empty())
+            .map(content),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(text: &str, editable: &[(Span, Editable)]) -> Page {
+        Page {
+            text: text.to_owned(),
+            labels: Vec::new(),
+            folding_ranges: Vec::new(),
+            goto_def: Vec::new(),
+            code_actions: Vec::new(),
+            diff_lines: Vec::new(),
+            changed_files: Vec::new(),
+            editable: editable.to_vec(),
+        }
+    }
+
+    fn description(change_id: &str) -> Editable {
+        Editable::Description {
+            change_id: change_id.to_owned(),
+        }
+    }
+
+    fn edited(page: &Page, text: &str) -> Vec<(String, usize, String)> {
+        page.edits(text)
+            .unwrap()
+            .into_iter()
+            .map(|(editable, offset, edited)| {
+                let Editable::Description { change_id } = editable else {
+                    panic!("unexpected editable {editable:?}");
+                };
+                (change_id.clone(), offset, edited.to_owned())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn edits_skip_unchanged_regions() {
+        let text = "A\none\n\nB\ntwo\n\n";
+        let page = page(text, &[(2..6, description("a")), (9..13, description("b"))]);
+
+        assert_eq!(edited(&page, text), []);
+        assert_eq!(
+            edited(&page, "A\none\n\nB\nthree\n\n"),
+            [("b".to_owned(), 9, "three\n".to_owned())]
+        );
+        assert_eq!(
+            edited(&page, "A\n\n\nB\ntwo\n\n"),
+            [("a".to_owned(), 2, "\n".to_owned())]
+        );
+        assert!(page.edits("A\none\n\nC\ntwo\n\n").is_err());
+    }
+}
@@ -22,7 +22,7 @@ impl Page for Annotate {
 
         let starting_commit = repo.revset_single("@")?;
         let template = repo.settings_annotation_template("templates.file_annotate")?;
-        let annotation = repo.annotation(&starting_commit, file_path)?;
+        let annotation = repo.annotation(&starting_commit, file_path, &out.cancelled)?;
 
         render_file_annotation(repo.inner(), out, &template, &annotation)?;
 
@@ -0,0 +1,82 @@
+use anyhow::Result;
+use std::io::Write;
+
+use crate::jj::Repo;
+use crate::page_writer::{CodeAction, Editable, GotoDefinitionTarget, PageWriter};
+use crate::semantic_token;
+
+use super::Page;
+
+/// Local and remote bookmarks, like `jj bookmark list --all-remotes`.
+pub struct Bookmarks;
+
+impl Page for Bookmarks {
+    fn name(&self) -> &'static str {
+        "bookmarks"
+    }
+
+    fn render(&self, out: &mut PageWriter, repo: &Repo, _: &[&str]) -> Result<()> {
+        let workspace = repo.workspace_dir();
+        let template = repo.settings_commit_ref_template("templates.bookmark_list")?;
+        let items = repo.bookmark_list()?;
+
+        write!(out.labelled(semantic_token::get("jjmagit")), "Bookmarks")?;
+        writeln!(out, " ({})", items.len())?;
+
+        for item in &items {
+            let folded = !item.tracked.is_empty();
+            if folded {
+                out.push_fold();
+            }
+            for commit_ref in std::iter::once(&item.primary).chain(&item.tracked) {
+                let name = commit_ref.name();
+                let actions = match commit_ref.remote_name() {
+                    None => vec![
+                        CodeAction::move_bookmark(workspace, name, "@"),
+                        CodeAction::move_bookmark(workspace, name, "@-"),
+                        CodeAction::delete_bookmark(workspace, name),
+                        CodeAction::forget_bookmark(workspace, name),
+                    ],
+                    Some(remote) if !repo.is_trackable_remote(remote) => vec![],
+                    Some(remote) if commit_ref.is_tracked() => {
+                        vec![CodeAction::untrack_bookmark(workspace, name, remote)]
+                    }
+                    Some(remote) => vec![CodeAction::track_bookmark(workspace, name, remote)],
+                };
+                let target = match commit_ref.target().as_normal() {
+                    Some(commit_id) => Some(repo.inner().store().get_commit(commit_id)?),
+                    None => None,
+                };
+
+                out.push_code_actions(actions);
+                if let Some(commit) = &target {
+                    out.goto_def
+                        .push(&out.buf, GotoDefinitionTarget::commit(repo, commit));
+                }
+                template.format(commit_ref, &mut out.formatter())?;
+                if target.is_some() {
+                    out.goto_def.pop(&out.buf);
+                }
+                out.pop_code_action();
+            }
+            if folded {
+                out.pop_fold();
+            }
+        }
+
+        writeln!(out)?;
+        writeln!(
+            out.labelled(semantic_token::get("jjmagit")),
+            "New bookmarks"
+        )?;
+        out.write_editable(Editable::NewBookmarks, "");
+        writeln!(out)?;
+        writeln!(
+            out.labelled(semantic_token::get("hint")),
+            "JJ: Add lines of the form \"<name> [<revision>]\" above and save this page to create \
+             bookmarks. The revision defaults to @."
+        )?;
+
+        Ok(())
+    }
+}
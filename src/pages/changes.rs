@@ -0,0 +1,119 @@
+use anyhow::Result;
+use jj_lib::commit::Commit;
+use jj_lib::conflicts::{MaterializedTreeDiffEntry, MaterializedTreeValue};
+use jj_lib::copies::CopyOperation;
+use jj_lib::diff_presentation::LineCompareMode;
+use jj_lib::matchers::FilesMatcher;
+use tower_lsp::lsp_types::Url;
+
+use crate::jj::{DiffState, Repo};
+use crate::page_writer::{ChangedFile, CodeAction, GotoDefinitionTarget, PageWriter};
+use crate::semantic_token;
+
+/// Writes one folded section per changed file, consisting of a status line and its git diff.
+pub(super) fn write_file_diffs(
+    out: &mut PageWriter,
+    repo: &Repo,
+    diff_state: &DiffState<'_>,
+    commit: &Commit,
+    entries: Vec<MaterializedTreeDiffEntry>,
+) -> Result<()> {
+    let workspace = repo.workspace_dir();
+
+    for item in entries {
+        let diff = item.values?;
+        let is_conflict = matches!(
+            diff.after,
+            MaterializedTreeValue::FileConflict(_) | MaterializedTreeValue::OtherConflict { .. }
+        );
+
+        let pretty_path = match item.path.to_diff() {
+            Some(paths) => repo.path_converter().format_copied_path(paths),
+            None => repo.path_converter().format_file_path(item.path.target()),
+        };
+
+        let mut paths = vec![item.path.source().to_owned(), item.path.target().to_owned()];
+        paths.dedup();
+        let mut actions = vec![
+            CodeAction::move_file_to_commit(workspace, commit, &pretty_path, &item.path),
+            CodeAction::restore_files(workspace, commit, &paths, &pretty_path),
+        ];
+        actions.extend(CodeAction::split_files(
+            workspace,
+            commit,
+            &paths,
+            &pretty_path,
+        ));
+
+        out.push_fold();
+        out.push_code_actions(actions);
+        let file_start = out.buf.len();
+
+        if let Some(op) = item.path.copy_operation() {
+            let (label, sigil) = match op {
+                CopyOperation::Copy => ("created", "C"),
+                CopyOperation::Rename => ("renamed", "R"),
+            };
+            writeln!(
+                out.labelled(semantic_token::get(label)),
+                "{sigil} {pretty_path}"
+            )?;
+        } else {
+            let path = repo.path_converter().format_file_path(item.path.target());
+
+            let base = repo.workspace_dir();
+
+            let target = GotoDefinitionTarget {
+                target: Url::from_file_path(item.path.target().to_fs_path(base)?).unwrap(),
+            };
+            out.goto_def.push(&out.buf, target);
+            match (diff.before.is_present(), diff.after.is_present()) {
+                _ if is_conflict => {
+                    let label = semantic_token::get("conflicted");
+                    writeln!(out.labelled(label), "U {path}")?
+                }
+                (true, true) => {
+                    let label = semantic_token::get("modified");
+                    writeln!(out.labelled(label), "M {path}")?
+                }
+                (false, true) => {
+                    let label = semantic_token::get("added");
+                    writeln!(out.labelled(label), "A {path}")?
+                }
+                (true, false) => {
+                    let label = semantic_token::get("deleted");
+                    writeln!(out.labelled(label), "D {path}")?
+                }
+                (false, false) => unreachable!(),
+            }
+            out.goto_def.pop(&out.buf);
+        }
+
+        out.changed_files.push((
+            file_start..out.buf.trim_end().len(),
+            ChangedFile {
+                commit: commit.clone(),
+                paths,
+            },
+        ));
+
+        let matcher = FilesMatcher::new([item.path.source(), item.path.target()]);
+        let diff_start = out.buf.len();
+        diff_state.write_diff(&mut out.formatter(), &matcher, LineCompareMode::Exact)?;
+        if is_conflict {
+            out.label_conflict_sides(diff_start);
+        }
+
+        // Hunks of copied files can't be selected on their own, since they span two paths.
+        if item.path.copy_operation().is_none() {
+            let path = item.path.target();
+            out.track_hunks(diff_start, commit, path, |hunk, checksum| {
+                CodeAction::hunk_actions(workspace, commit, path, hunk, checksum, "hunk")
+            });
+        }
+        out.pop_fold();
+        out.pop_code_action();
+    }
+
+    Ok(())
+}
@@ -1,8 +1,29 @@
 use crate::jj::Repo;
-use crate::page_writer::PageWriter;
+use crate::page_writer::{CodeAction, Editable, GotoDefinitionTarget, PageWriter};
+use crate::semantic_token;
 use anyhow::{Result, anyhow};
+use jj_lib::matchers::EverythingMatcher;
 use std::io::Write;
 
+/// jj's `builtin_log_detailed` without the description, which follows as an editable region.
+const HEADER_TEMPLATE: &str = r#"
+concat(
+  "Commit ID: " ++ self.commit_id() ++ "\n",
+  "Change ID: " ++ self.change_id() ++ "\n",
+  surround("Bookmarks: ", "\n", separate(" ",
+    self.local_bookmarks(),
+    self.remote_bookmarks(),
+  )),
+  surround("Tags     : ", "\n", self.tags()),
+  "Author   : " ++ format_detailed_signature(self.author()) ++ "\n",
+  "Committer: " ++ format_detailed_signature(self.committer()) ++ "\n",
+  if(config("ui.show-cryptographic-signatures").as_boolean(),
+    "Signature: " ++ format_detailed_cryptographic_signature(self.signature()) ++ "\n",
+  ),
+  "\n",
+)
+"#;
+
 pub struct Commit;
 
 impl super::Page for Commit {
@@ -10,13 +31,65 @@ impl super::Page for Commit {
         "commit"
     }
 
-    fn render(&self, out: &mut PageWriter, _repo: &Repo, options: &[&str]) -> Result<()> {
+    fn render(&self, out: &mut PageWriter, repo: &Repo, options: &[&str]) -> Result<()> {
         let [change_id] = options else {
             return Err(anyhow!("Expected 1 argument change_id, got {:?}", options,));
         };
 
-        writeln!(out, "Commit: {change_id}")?;
-        writeln!(out, "todo")?;
+        let commit = repo.revset_single(change_id)?;
+
+        let header_template = repo.commit_template(HEADER_TEMPLATE)?;
+        out.push_code_action(CodeAction::create_bookmark(repo.workspace_dir(), &commit));
+        out.goto_def
+            .push(&out.buf, GotoDefinitionTarget::evolog(repo, &commit));
+        header_template.format(&commit, &mut out.formatter())?;
+        out.goto_def.pop(&out.buf);
+        out.pop_code_action();
+
+        out.goto_def
+            .push(&out.buf, GotoDefinitionTarget::describe(repo, &commit));
+        write!(out.labelled(semantic_token::get("jjmagit")), "Description")?;
+        out.goto_def.pop(&out.buf);
+        writeln!(out)?;
+        out.write_editable(
+            Editable::Description {
+                change_id: commit.change_id().to_string(),
+            },
+            commit.description(),
+        );
+        writeln!(out)?;
+
+        let summary_template = repo.settings_commit_template("templates.commit_summary")?;
+        let parents = commit.parents().collect::<Result<Vec<_>, _>>()?;
+        let children = repo.children(&commit)?;
+        for (heading, commits) in [("Parents", parents), ("Children", children)] {
+            if commits.is_empty() {
+                continue;
+            }
+
+            writeln!(out.labelled(semantic_token::get("jjmagit")), "{heading}")?;
+            for commit in commits {
+                out.goto_def
+                    .push(&out.buf, GotoDefinitionTarget::commit(repo, &commit));
+                out.push_code_actions(vec![
+                    CodeAction::new(repo.workspace_dir(), &commit),
+                    CodeAction::abandon(repo.workspace_dir(), &commit),
+                ]);
+                summary_template.format(&commit, &mut out.formatter())?;
+                out.pop_code_action();
+                out.goto_def.pop(&out.buf);
+                writeln!(out)?;
+            }
+            writeln!(out)?;
+        }
+
+        let diff_state = repo.diff(&commit)?;
+        let diff = diff_state.diff(&EverythingMatcher)?;
+
+        write!(out.labelled(semantic_token::get("jjmagit")), "Changes")?;
+        writeln!(out, " ({})", diff.len())?;
+
+        super::changes::write_file_diffs(out, repo, &diff_state, &commit, diff)?;
 
         Ok(())
     }
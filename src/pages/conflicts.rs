@@ -0,0 +1,101 @@
+use anyhow::{Result, anyhow};
+use std::io::Write;
+
+use crate::jj::Repo;
+use crate::page_writer::{CodeAction, GotoDefinitionTarget, PageWriter};
+use crate::semantic_token;
+
+use super::Page;
+
+/// The conflicted files of `@`, or of the commits of a revset, like `jj resolve --list`.
+pub struct Conflicts;
+
+impl Page for Conflicts {
+    fn name(&self) -> &'static str {
+        "conflicts"
+    }
+
+    fn render(&self, out: &mut PageWriter, repo: &Repo, options: &[&str]) -> Result<()> {
+        let revset_string = match options {
+            [] => "@",
+            [revset] => revset,
+            _ => {
+                return Err(anyhow!(
+                    "Expected optional argument revset, got {:?}",
+                    options
+                ));
+            }
+        };
+
+        let workspace = repo.workspace_dir();
+        let current_commit = repo.current_commit()?;
+        let commits = repo
+            .revset_expression(&format!("({revset_string}) & conflicts()"))?
+            .evaluate_to_commits()?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        write!(out.labelled(semantic_token::get("jjmagit")), "Conflicts")?;
+        writeln!(out, " ({})", commits.len())?;
+
+        for commit in commits {
+            let conflicts = repo.conflicts(&commit)?;
+            let parents = commit.parents().collect::<Result<Vec<_>, _>>()?;
+            let labels = commit.tree().labels().clone();
+
+            out.push_fold();
+            out.goto_def
+                .push(&out.buf, GotoDefinitionTarget::commit(repo, &commit));
+            repo.write_log(&mut out.formatter(), &commit)?;
+            out.goto_def.pop(&out.buf);
+
+            for (path, conflict) in conflicts {
+                let pretty_path = repo.path_converter().format_file_path(&path);
+                let sides = conflict.num_sides();
+                // Labels describe the sides of the tree, which are only the sides of the path's
+                // conflict if it didn't simplify.
+                let labels = (labels.num_sides() == Some(sides)).then_some(&labels);
+
+                let mut actions = (0..sides)
+                    .map(|side| {
+                        let label = labels.and_then(|labels| labels.get_add(side));
+                        CodeAction::resolve_conflict(
+                            workspace,
+                            &commit,
+                            &path,
+                            &pretty_path,
+                            side,
+                            label,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                actions.extend(parents.iter().map(|parent| {
+                    CodeAction::restore_file(workspace, &commit, parent, &path, &pretty_path)
+                }));
+
+                out.push_code_actions(actions);
+                // Only the working-copy commit has its conflicts materialized on disk.
+                let target = if commit.id() == current_commit.id() {
+                    GotoDefinitionTarget::file(repo, &path)
+                } else {
+                    GotoDefinitionTarget::commit(repo, &commit)
+                };
+                out.goto_def.push(&out.buf, target);
+                write!(
+                    out.labelled(semantic_token::get("conflicted")),
+                    "{pretty_path}"
+                )?;
+                out.goto_def.pop(&out.buf);
+                write!(out, " ")?;
+                write!(
+                    out.labelled(semantic_token::get("conflict_description")),
+                    "{sides}-sided conflict"
+                )?;
+                out.pop_code_action();
+                writeln!(out)?;
+            }
+            out.pop_fold();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::{Result, anyhow};
+use std::io::Write;
+
+use crate::jj::Repo;
+use crate::page_writer::{Editable, GotoDefinitionTarget, PageWriter};
+use crate::semantic_token;
+
+use super::Page;
+
+/// The description of a single commit, which is updated when the page is saved.
+pub struct Describe;
+
+impl Page for Describe {
+    fn name(&self) -> &'static str {
+        "describe"
+    }
+
+    fn render(&self, out: &mut PageWriter, repo: &Repo, options: &[&str]) -> Result<()> {
+        let [change_id] = options else {
+            return Err(anyhow!("Expected 1 argument change_id, got {:?}", options));
+        };
+
+        let commit = repo.revset_single(change_id)?;
+
+        out.write_editable(
+            Editable::Description {
+                change_id: commit.change_id().to_string(),
+            },
+            commit.description(),
+        );
+        writeln!(out)?;
+
+        let summary_template = repo.settings_commit_template("templates.commit_summary")?;
+        write!(out.labelled(semantic_token::get("hint")), "JJ: ")?;
+        out.goto_def
+            .push(&out.buf, GotoDefinitionTarget::commit(repo, &commit));
+        summary_template.format(&commit, &mut out.formatter())?;
+        out.goto_def.pop(&out.buf);
+        writeln!(out)?;
+        writeln!(
+            out.labelled(semantic_token::get("hint")),
+            "JJ: Save this page to update the description. Lines starting with \"JJ:\" are removed."
+        )?;
+
+        Ok(())
+    }
+}
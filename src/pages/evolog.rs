@@ -0,0 +1,83 @@
+use anyhow::{Result, anyhow};
+use jj_lib::commit::Commit;
+use jj_lib::diff_presentation::LineCompareMode;
+use jj_lib::matchers::EverythingMatcher;
+use std::io::Write;
+
+use crate::jj::Repo;
+use crate::page_writer::PageWriter;
+use crate::semantic_token;
+
+use super::Page;
+
+/// The previous versions of a change, each with the changes made to its predecessors, like
+/// `jj evolog --patch`.
+pub struct Evolog;
+
+impl Page for Evolog {
+    fn name(&self) -> &'static str {
+        "evolog"
+    }
+
+    fn render(&self, out: &mut PageWriter, repo: &Repo, options: &[&str]) -> Result<()> {
+        let [change_id] = options else {
+            return Err(anyhow!("Expected 1 argument change_id, got {:?}", options));
+        };
+
+        let commit = repo.revset_single(change_id)?;
+        let template = repo.settings_evolog_template("templates.evolog")?;
+
+        let entries = repo.evolution(&commit)?;
+        write!(out.labelled(semantic_token::get("jjmagit")), "Versions")?;
+        writeln!(out, " ({})", entries.len())?;
+
+        for entry in entries {
+            out.push_fold();
+            template.format(&entry, &mut out.formatter())?;
+
+            let predecessors = entry
+                .predecessor_ids()
+                .iter()
+                .map(|id| repo.inner().store().get_commit(id))
+                .collect::<Result<Vec<_>, _>>()?;
+            if !predecessors.is_empty() {
+                write_description_change(out, &predecessors, &entry.commit)?;
+                let diff_state = repo.inter_diff(&predecessors, &entry.commit)?;
+                diff_state.write_diff(
+                    &mut out.formatter(),
+                    &EverythingMatcher,
+                    LineCompareMode::IgnoreAllSpace,
+                )?;
+            }
+            out.pop_fold();
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the old and new description, if `commit` changed the description of its predecessors.
+fn write_description_change(
+    out: &mut PageWriter,
+    predecessors: &[Commit],
+    commit: &Commit,
+) -> Result<()> {
+    if predecessors
+        .iter()
+        .all(|predecessor| predecessor.description() == commit.description())
+    {
+        return Ok(());
+    }
+
+    writeln!(out.labelled(semantic_token::get("header")), "Description:")?;
+    for predecessor in predecessors {
+        for line in predecessor.description().lines() {
+            writeln!(out.labelled(semantic_token::get("removed")), "-{line}")?;
+        }
+    }
+    for line in commit.description().lines() {
+        writeln!(out.labelled(semantic_token::get("added")), "+{line}")?;
+    }
+
+    Ok(())
+}
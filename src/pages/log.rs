@@ -0,0 +1,159 @@
+use anyhow::{Result, anyhow};
+use jj_cli::formatter::PlainTextFormatter;
+use jj_cli::graphlog::{GraphStyle, get_graphlog};
+use jj_lib::graph::{GraphEdge, GraphEdgeType};
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::jj::Repo;
+use crate::page_writer::{CodeAction, GotoDefinitionTarget, PageWriter};
+use crate::semantic_token;
+
+use super::Page;
+
+/// The commit graph of a revset, `revsets.log` by default, like `jj log`.
+pub struct Log;
+
+impl Page for Log {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    fn render(&self, out: &mut PageWriter, repo: &Repo, options: &[&str]) -> Result<()> {
+        let revset_string = match options {
+            [] => repo.settings().get_string("revsets.log")?,
+            [revset] => revset.to_string(),
+            _ => {
+                return Err(anyhow!(
+                    "Expected optional argument revset, got {:?}",
+                    options
+                ));
+            }
+        };
+
+        let template = repo.settings_commit_template("templates.log")?;
+        let node_template = repo.settings_node_template("templates.log_node")?;
+        let graph_style = GraphStyle::from_settings(repo.settings())?;
+        let use_elided_nodes = repo.settings().get_bool("ui.log-synthetic-elided-nodes")?;
+
+        // The graph writes each row to `rows`, which is drained into the page after every node.
+        let rows = SharedBuffer::default();
+        let mut graph_output = rows.clone();
+        let mut graph = get_graphlog(graph_style, &mut graph_output);
+
+        let store = repo.inner().store();
+        let current_commit_id = repo.current_commit()?.id().clone();
+        for (commit_id, edges) in repo.log_graph(&revset_string, &out.cancelled)? {
+            // The graph is keyed by (CommitId, is_synthetic), like `jj log`.
+            let mut graph_edges = Vec::new();
+            let mut missing_edge_id = None;
+            let mut elided_targets = Vec::new();
+            for edge in edges {
+                match edge.edge_type {
+                    GraphEdgeType::Missing => missing_edge_id = Some(edge.target),
+                    GraphEdgeType::Direct => {
+                        graph_edges.push(GraphEdge::direct((edge.target, false)));
+                    }
+                    GraphEdgeType::Indirect if use_elided_nodes => {
+                        elided_targets.push(edge.target.clone());
+                        graph_edges.push(GraphEdge::direct((edge.target, true)));
+                    }
+                    GraphEdgeType::Indirect => {
+                        graph_edges.push(GraphEdge::indirect((edge.target, false)));
+                    }
+                }
+            }
+            if let Some(missing_edge_id) = missing_edge_id {
+                graph_edges.push(GraphEdge::missing((missing_edge_id, false)));
+            }
+
+            let commit = store.get_commit(&commit_id)?;
+
+            let mut content = PageWriter {
+                debug: out.debug,
+                cancelled: Arc::clone(&out.cancelled),
+                ..Default::default()
+            };
+            content
+                .goto_def
+                .push(&content.buf, GotoDefinitionTarget::commit(repo, &commit));
+            let mut actions = vec![
+                CodeAction::new(repo.workspace_dir(), &commit),
+                CodeAction::edit(repo.workspace_dir(), &commit),
+                CodeAction::duplicate(repo.workspace_dir(), &commit),
+                CodeAction::abandon(repo.workspace_dir(), &commit),
+                CodeAction::create_bookmark(repo.workspace_dir(), &commit),
+            ];
+            actions.extend(CodeAction::rebase_actions(repo.workspace_dir(), &commit));
+            if commit.id() == &current_commit_id {
+                actions.extend(CodeAction::movement_actions(repo.workspace_dir()));
+            }
+            content.push_code_actions(actions);
+            template.format(&commit, &mut content.formatter())?;
+            content.pop_code_action();
+            content.goto_def.pop(&content.buf);
+
+            let mut node_symbol = Vec::new();
+            node_template.format(
+                &Some(commit),
+                &mut PlainTextFormatter::new(&mut node_symbol),
+            )?;
+
+            graph.add_node(
+                &(commit_id, false),
+                &graph_edges,
+                &String::from_utf8_lossy(&node_symbol),
+                &content.buf,
+            )?;
+            out.push_fold();
+            out.write_graph_row(&rows.take(), content);
+            out.pop_fold();
+
+            for elided_target in elided_targets {
+                let elided_key = (elided_target, true);
+                let edges = [GraphEdge::direct((elided_key.0.clone(), false))];
+
+                let mut content = PageWriter::default();
+                writeln!(
+                    content.labelled(semantic_token::get("elided")),
+                    "(elided revisions)"
+                )?;
+
+                let mut node_symbol = Vec::new();
+                node_template.format(&None, &mut PlainTextFormatter::new(&mut node_symbol))?;
+
+                graph.add_node(
+                    &elided_key,
+                    &edges,
+                    &String::from_utf8_lossy(&node_symbol),
+                    &content.buf,
+                )?;
+                out.write_graph_row(&rows.take(), content);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An in-memory writer that can be read while the graph renderer holds on to it.
+#[derive(Clone, Default)]
+pub(super) struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub(super) fn take(&self) -> String {
+        String::from_utf8_lossy(&self.0.take()).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -3,14 +3,34 @@ use crate::page_writer::PageWriter;
 use anyhow::Result;
 
 mod annotate;
+mod bookmarks;
+mod changes;
 mod commit;
+mod conflicts;
+mod describe;
+mod evolog;
+mod log;
+mod operation;
+mod oplog;
+mod rebase;
 mod status;
 
 pub use annotate::Annotate;
+pub use bookmarks::Bookmarks;
 pub use commit::Commit;
+pub use conflicts::Conflicts;
+pub use describe::Describe;
+pub use evolog::Evolog;
+pub use log::Log;
+pub use operation::Operation;
+pub use oplog::Oplog;
+pub use rebase::Rebase;
 pub use status::Status;
 
-pub const ALL: &[&dyn Page] = &[&Status, &Annotate, &Commit];
+pub const ALL: &[&dyn Page] = &[
+    &Status, &Annotate, &Commit, &Describe, &Log, &Oplog, &Operation, &Bookmarks, &Evolog,
+    &Conflicts, &Rebase,
+];
 
 pub fn named(name: &str) -> Option<&'static dyn Page> {
     ALL.iter()
@@ -25,26 +45,58 @@ pub trait Page: Send + Sync {
 }
 
 pub mod path {
-    use anyhow::{Context, Result, ensure};
+    use anyhow::{Context, Result, anyhow, bail, ensure};
     use std::path::{Path, PathBuf};
+    use tower_lsp::lsp_types::Url;
 
     use crate::pages::{self, truncate_end_matches};
 
     use super::Page;
 
-    const _ESCAPE_CHARS: &str = r#"<>:"/\|?*"#;
+    /// The URI scheme of pages. Pages are virtual documents served by the language server,
+    /// addressed by the path they would have inside the workspace's `.jj` directory.
+    pub const SCHEME: &str = "jjmagit";
+
+    /// Characters that can't appear in file names on some platforms, plus the ones used by the
+    /// encoding itself. `/` is common in file paths, so it is encoded more readably as `'`.
+    const ESCAPE_CHARS: &str = r#"<>:"\|?*%'"#;
 
     fn encode_argument(arg: &str) -> String {
-        if r#"<>:"\|?*"#.chars().any(|c| arg.contains(c)) {
-            todo!()
+        let mut encoded = String::with_capacity(arg.len());
+        for (i, c) in arg.char_indices() {
+            if ESCAPE_CHARS.contains(c) || (i == 0 && c == '.') {
+                encoded.push_str(&format!("%{:02X}", c as u32));
+            } else if c == '/' {
+                encoded.push('\'');
+            } else {
+                encoded.push(c);
+            }
         }
-        arg.replace('/', "'")
+        encoded
     }
     fn decode_argument(arg: &str) -> String {
-        arg.replace('\'', "/")
+        let mut decoded = String::with_capacity(arg.len());
+        let mut rest = arg;
+        while let Some(c) = rest.chars().next() {
+            let escaped = rest
+                .strip_prefix('%')
+                .and_then(|hex| hex.get(..2))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match (c, escaped) {
+                ('%', Some(byte)) => {
+                    decoded.push(byte as char);
+                    rest = &rest[3..];
+                }
+                _ => {
+                    decoded.push(if c == '\'' { '/' } else { c });
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        decoded
     }
 
-    pub fn get_path(workspace: &Path, page: &dyn Page, arguments: &[&str]) -> PathBuf {
+    fn get_path(workspace: &Path, page: &dyn Page, arguments: &[&str]) -> PathBuf {
         let mut page_path = workspace.to_path_buf();
         page_path.push(".jj");
 
@@ -60,7 +112,39 @@ pub mod path {
         page_path
     }
 
-    pub fn parse_path(path: &Path) -> Result<(PathBuf, &'static dyn Page, Vec<String>)> {
+    pub fn get_uri(workspace: &Path, page: &dyn Page, arguments: &[&str]) -> Url {
+        let path = get_path(workspace, page, arguments);
+        let file_url = Url::from_file_path(path).expect("workspace path is absolute");
+        Url::parse(&format!("{SCHEME}://{}", file_url.path())).expect("file url path is valid")
+    }
+
+    /// Parses the URI of a page into its workspace, page and arguments.
+    ///
+    /// Besides `jjmagit:` URIs, this accepts `file:` URIs of the same path for editors that
+    /// can only open files, like Zed. Nothing is written to that path, the server fills in the
+    /// contents of the opened document.
+    pub fn parse_uri(uri: &Url) -> Result<(PathBuf, &'static dyn Page, Vec<String>)> {
+        let path = match uri.scheme() {
+            SCHEME => Url::parse(&format!("file://{}", uri.path()))?.to_file_path(),
+            "file" => uri.to_file_path(),
+            _ => bail!("{uri} is not a page"),
+        }
+        .map_err(|()| anyhow!("{uri} is not a page"))?;
+        ensure!(
+            path.extension()
+                .is_some_and(|extension| extension == "jjmagit"),
+            "{uri} is not a page"
+        );
+
+        parse_path(&path)
+    }
+
+    /// `target` with the scheme of `origin`, so that pages opened as files link to files.
+    pub fn with_scheme_of(target: &Url, origin: &Url) -> Url {
+        Url::parse(&format!("{}://{}", origin.scheme(), target.path())).unwrap_or(target.clone())
+    }
+
+    fn parse_path(path: &Path) -> Result<(PathBuf, &'static dyn Page, Vec<String>)> {
         let mut jj_path = PathBuf::new();
         let mut components = path
             .components()
@@ -96,6 +180,77 @@ pub mod path {
 
         Ok((jj_path, page, arguments))
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const ARGUMENTS: &[&str] = &[
+            "plain",
+            "src/main.rs",
+            "with space",
+            "100%",
+            "%41",
+            "it's",
+            ".hidden",
+            "a<b>:c\\d|e?f*g\"h",
+            "ünïcödé/日本語",
+        ];
+
+        #[test]
+        fn argument_round_trip() {
+            for argument in ARGUMENTS {
+                let encoded = encode_argument(argument);
+                assert!(!encoded.contains('/'), "{encoded}");
+                assert!(!encoded.starts_with('.'), "{encoded}");
+                assert_eq!(decode_argument(&encoded), *argument);
+            }
+        }
+
+        #[test]
+        fn uri_round_trip() {
+            let workspace = Path::new("/home/user/my repo/ünï %20");
+            for argument in ARGUMENTS {
+                let uri = get_uri(workspace, &pages::Annotate, &[argument]);
+                assert_eq!(uri.scheme(), SCHEME);
+
+                let (parsed_workspace, page, arguments) = parse_uri(&uri).unwrap();
+                assert_eq!(parsed_workspace, workspace);
+                assert_eq!(page.name(), "annotate");
+                assert_eq!(arguments, [*argument]);
+            }
+
+            let uri = get_uri(workspace, &pages::Status, &[]);
+            let (parsed_workspace, page, arguments) = parse_uri(&uri).unwrap();
+            assert_eq!(parsed_workspace, workspace);
+            assert_eq!(page.name(), "status");
+            assert!(arguments.is_empty());
+
+            let uri = get_uri(workspace, &pages::Commit, &["a b", "c%d"]);
+            let (_, page, arguments) = parse_uri(&uri).unwrap();
+            assert_eq!(page.name(), "commit");
+            assert_eq!(arguments, ["a b", "c%d"]);
+        }
+
+        #[test]
+        fn file_uri() {
+            let workspace = Path::new("/home/user/my repo");
+            let uri = get_uri(workspace, &pages::Annotate, &["src/ä b.rs"]);
+            let file_uri = with_scheme_of(&uri, &Url::parse("file:///").unwrap());
+            assert_eq!(file_uri.scheme(), "file");
+
+            let (parsed_workspace, page, arguments) = parse_uri(&file_uri).unwrap();
+            assert_eq!(parsed_workspace, workspace);
+            assert_eq!(page.name(), "annotate");
+            assert_eq!(arguments, ["src/ä b.rs"]);
+            assert_eq!(with_scheme_of(&file_uri, &uri), uri);
+
+            let not_a_page = Url::from_file_path("/home/user/my repo/.jj/repo/op_heads").unwrap();
+            assert!(parse_uri(&not_a_page).is_err());
+            let other_scheme = Url::parse("untitled:///.jj/status.jjmagit").unwrap();
+            assert!(parse_uri(&other_scheme).is_err());
+        }
+    }
 }
 
 fn truncate_end_matches(buf: &mut String, suffix: &str) {
@@ -0,0 +1,97 @@
+use anyhow::{Result, anyhow};
+use jj_lib::op_store::RefTarget;
+use std::io::Write;
+
+use crate::jj::Repo;
+use crate::page_writer::{CodeAction, GotoDefinitionTarget, PageWriter};
+use crate::semantic_token;
+
+use super::Page;
+
+/// A single operation and the commits and bookmarks it changed, like `jj op show`.
+pub struct Operation;
+
+impl Page for Operation {
+    fn name(&self) -> &'static str {
+        "operation"
+    }
+
+    fn render(&self, out: &mut PageWriter, repo: &Repo, options: &[&str]) -> Result<()> {
+        let [op_id] = options else {
+            return Err(anyhow!(
+                "Expected 1 argument operation_id, got {:?}",
+                options
+            ));
+        };
+
+        let operation = repo.operation(op_id)?;
+
+        out.push_code_actions(vec![
+            CodeAction::undo_operation(repo.workspace_dir(), &operation),
+            CodeAction::restore_operation(repo.workspace_dir(), &operation),
+        ]);
+        let show_template = repo.settings_operation_template("templates.op_show")?;
+        show_template.format(&operation, &mut out.formatter())?;
+        out.pop_code_action();
+        writeln!(out)?;
+
+        let changes = repo.operation_changes(&operation)?;
+        let summary_template = repo.settings_commit_template("templates.commit_summary")?;
+
+        write!(
+            out.labelled(semantic_token::get("jjmagit")),
+            "Changed commits"
+        )?;
+        writeln!(out, " ({})", changes.added.len() + changes.removed.len())?;
+        for (sign, label, commits) in [
+            ("+", "added", &changes.added),
+            ("-", "removed", &changes.removed),
+        ] {
+            for commit in commits {
+                write!(out.labelled(semantic_token::get(label)), "{sign}")?;
+                write!(out, " ")?;
+                out.goto_def
+                    .push(&out.buf, GotoDefinitionTarget::commit(repo, commit));
+                summary_template.format(commit, &mut out.formatter())?;
+                out.goto_def.pop(&out.buf);
+                writeln!(out)?;
+            }
+        }
+
+        if !changes.bookmarks.is_empty() {
+            writeln!(out)?;
+            write!(
+                out.labelled(semantic_token::get("jjmagit")),
+                "Changed bookmarks"
+            )?;
+            writeln!(out, " ({})", changes.bookmarks.len())?;
+            for (name, before, after) in &changes.bookmarks {
+                write!(
+                    out.labelled(semantic_token::get("bookmark")),
+                    "{}",
+                    name.as_symbol()
+                )?;
+                writeln!(
+                    out,
+                    ": {} -> {}",
+                    format_target(before),
+                    format_target(after)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn format_target(target: &RefTarget) -> String {
+    let ids: Vec<String> = target
+        .added_ids()
+        .map(|id| format!("{:.12}", id.to_string()))
+        .collect();
+    match ids.as_slice() {
+        [] => "(absent)".into(),
+        [id] if !target.has_conflict() => id.clone(),
+        _ => format!("{} (conflicted)", ids.join(", ")),
+    }
+}
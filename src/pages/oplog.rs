@@ -0,0 +1,71 @@
+use anyhow::Result;
+use jj_cli::formatter::PlainTextFormatter;
+use jj_cli::graphlog::{GraphStyle, get_graphlog};
+use jj_lib::graph::GraphEdge;
+use std::sync::Arc;
+
+use crate::jj::Repo;
+use crate::page_writer::{CodeAction, GotoDefinitionTarget, PageWriter};
+
+use super::Page;
+use super::log::SharedBuffer;
+
+/// The operation log of the repo, like `jj op log`.
+pub struct Oplog;
+
+impl Page for Oplog {
+    fn name(&self) -> &'static str {
+        "oplog"
+    }
+
+    fn render(&self, out: &mut PageWriter, repo: &Repo, _: &[&str]) -> Result<()> {
+        let template = repo.settings_operation_template("templates.op_log")?;
+        let node_template = repo.settings_operation_template("templates.op_log_node")?;
+        let graph_style = GraphStyle::from_settings(repo.settings())?;
+
+        let rows = SharedBuffer::default();
+        let mut graph_output = rows.clone();
+        let mut graph = get_graphlog(graph_style, &mut graph_output);
+
+        for operation in repo.operations()? {
+            let edges: Vec<_> = operation
+                .parent_ids()
+                .iter()
+                .cloned()
+                .map(GraphEdge::direct)
+                .collect();
+
+            let mut content = PageWriter {
+                debug: out.debug,
+                cancelled: Arc::clone(&out.cancelled),
+                ..Default::default()
+            };
+            content.goto_def.push(
+                &content.buf,
+                GotoDefinitionTarget::operation(repo, &operation),
+            );
+            content.push_code_actions(vec![
+                CodeAction::undo_operation(repo.workspace_dir(), &operation),
+                CodeAction::restore_operation(repo.workspace_dir(), &operation),
+            ]);
+            template.format(&operation, &mut content.formatter())?;
+            content.pop_code_action();
+            content.goto_def.pop(&content.buf);
+
+            let mut node_symbol = Vec::new();
+            node_template.format(&operation, &mut PlainTextFormatter::new(&mut node_symbol))?;
+
+            graph.add_node(
+                operation.id(),
+                &edges,
+                &String::from_utf8_lossy(&node_symbol),
+                &content.buf,
+            )?;
+            out.push_fold();
+            out.write_graph_row(&rows.take(), content);
+            out.pop_fold();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::{Result, anyhow};
+use std::io::Write;
+
+use crate::jj::Repo;
+use crate::page_writer::{Editable, GotoDefinitionTarget, PageWriter};
+use crate::semantic_token;
+
+use super::Page;
+
+/// A linear chain of commits, `trunk()..@` by default, one `pick` line each. Saving the page
+/// applies the edited plan, like `git rebase --interactive`.
+pub struct Rebase;
+
+impl Page for Rebase {
+    fn name(&self) -> &'static str {
+        "rebase"
+    }
+
+    fn render(&self, out: &mut PageWriter, repo: &Repo, options: &[&str]) -> Result<()> {
+        let revset_string = match options {
+            [] => "trunk()..@",
+            [revset] => revset,
+            _ => {
+                return Err(anyhow!(
+                    "Expected optional argument revset, got {:?}",
+                    options
+                ));
+            }
+        };
+
+        let commits = repo.linear_commits(revset_string)?;
+        let summary_template = repo.settings_commit_template("templates.commit_summary")?;
+
+        write!(out.labelled(semantic_token::get("jjmagit")), "Rebase onto")?;
+        write!(out, " ")?;
+        match commits.first() {
+            Some(first) => {
+                for parent in first.parents() {
+                    let parent = parent?;
+                    out.goto_def
+                        .push(&out.buf, GotoDefinitionTarget::commit(repo, &parent));
+                    summary_template.format(&parent, &mut out.formatter())?;
+                    out.goto_def.pop(&out.buf);
+                    writeln!(out)?;
+                }
+            }
+            None => writeln!(out.labelled(semantic_token::get("elided")), "(no commits)")?,
+        }
+
+        let plan: String = commits
+            .iter()
+            .map(|commit| {
+                let change_id = commit.change_id().to_string();
+                let subject = commit.description().lines().next().unwrap_or_default();
+                format!("pick {:.12} {subject}", change_id)
+                    .trim_end()
+                    .to_owned()
+                    + "\n"
+            })
+            .collect();
+        out.write_editable(
+            Editable::RebasePlan {
+                change_ids: commits
+                    .iter()
+                    .map(|commit| commit.change_id().to_string())
+                    .collect(),
+            },
+            &plan,
+        );
+        writeln!(out)?;
+
+        for hint in [
+            "JJ: Save this page to rebase the commits from top to bottom. Removing a line drops",
+            "JJ: the commit.",
+            "JJ:",
+            "JJ: pick <change> = keep the commit",
+            "JJ: reword <change> <subject> = keep the commit with a new first description line",
+            "JJ: squash <change> = meld the commit into the one above it",
+            "JJ: drop <change> = abandon the commit",
+        ] {
+            writeln!(out.labelled(semantic_token::get("hint")), "{hint}")?;
+        }
+
+        Ok(())
+    }
+}
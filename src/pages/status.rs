@@ -1,8 +1,6 @@
 use anyhow::Result;
-use jj_lib::copies::CopyOperation;
-use jj_lib::matchers::{EverythingMatcher, FilesMatcher};
+use jj_lib::matchers::EverythingMatcher;
 use std::io::Write;
-use tower_lsp::lsp_types::Url;
 
 use crate::jj::Repo;
 use crate::page_writer::{CodeAction, GotoDefinitionTarget, PageWriter};
@@ -23,91 +21,52 @@ impl Page for Status {
         let diff_state = repo.diff(&commit)?;
         let diff = diff_state.diff(&EverythingMatcher)?;
 
-        write!(out.labelled(semantic_token::get("jjmagit")), "Head: ")?;
+        out.goto_def
+            .push(&out.buf, GotoDefinitionTarget::evolog(repo, &commit));
+        write!(out.labelled(semantic_token::get("jjmagit")), "Head:")?;
+        out.goto_def.pop(&out.buf);
+        write!(out, " ")?;
 
+        let mut actions = CodeAction::movement_actions(repo.workspace_dir());
+        actions.push(CodeAction::duplicate(repo.workspace_dir(), &commit));
+        out.push_code_actions(actions);
         repo.write_log(&mut out.formatter(), &commit)?;
+        out.pop_code_action();
         writeln!(out)?;
 
-        out.push_code_action(CodeAction::move_to_commit());
+        out.push_code_action(CodeAction::move_to_commit(repo.workspace_dir(), &commit));
         write!(out.labelled(semantic_token::get("jjmagit")), "Changes")?;
         out.pop_code_action();
 
         writeln!(out, " ({})", diff.len(),)?;
 
-        for item in diff {
-            let diff = item.values?;
-
-            let pretty_path = match item.path.to_diff() {
-                Some(paths) => repo.path_converter().format_copied_path(paths),
-                None => repo.path_converter().format_file_path(item.path.target()),
-            };
-
-            out.push_fold();
-            out.push_code_action(CodeAction::move_file_to_commit(pretty_path.clone()));
-
-            if let Some(op) = item.path.copy_operation() {
-                let (label, sigil) = match op {
-                    CopyOperation::Copy => ("created", "C"),
-                    CopyOperation::Rename => ("renamed", "R"),
-                };
-                writeln!(
-                    out.labelled(semantic_token::get(label)),
-                    "{sigil} {pretty_path}"
-                )?;
-            } else {
-                let path = repo.path_converter().format_file_path(item.path.target());
-
-                let base = repo.workspace_dir();
-
-                let target = GotoDefinitionTarget {
-                    target: Url::from_file_path(item.path.target().to_fs_path(base)?).unwrap(),
-                };
-                out.goto_def.push(&out.buf, target);
-                match (diff.before.is_present(), diff.after.is_present()) {
-                    (true, true) => {
-                        let label = semantic_token::get("modified");
-                        writeln!(out.labelled(label), "M {path}")?
-                    }
-                    (false, true) => {
-                        let label = semantic_token::get("added");
-                        writeln!(out.labelled(label), "A {path}")?
-                    }
-                    (true, false) => {
-                        let label = semantic_token::get("deleted");
-                        writeln!(out.labelled(label), "D {path}")?
-                    }
-                    (false, false) => unreachable!(),
-                }
-                out.goto_def.pop(&out.buf);
-            }
-
-            let matcher = FilesMatcher::new([item.path.source(), item.path.target()]);
-            diff_state.write_diff(&mut out.formatter(), &matcher)?;
-            out.pop_fold();
-            out.pop_code_action();
-        }
+        super::changes::write_file_diffs(out, repo, &diff_state, &commit, diff)?;
 
         writeln!(out)?;
-        writeln!(
+        out.goto_def.push(
+            &out.buf,
+            GotoDefinitionTarget::page(repo.workspace_dir(), &super::Log, &[]),
+        );
+        write!(
             out.labelled(semantic_token::get("jjmagit")),
             "Recent commits"
         )?;
+        out.goto_def.pop(&out.buf);
+        writeln!(out)?;
 
         let log = repo.log()?;
         for commit in log {
             out.push_fold();
 
-            let target = GotoDefinitionTarget {
-                target: Url::from_file_path(
-                    repo.workspace_dir()
-                        .join(format!(".jj/commit/{}.jjmagit", commit.change_id())),
-                )
-                .unwrap(),
-            };
-
-            out.goto_def.push(&out.buf, target);
+            out.goto_def
+                .push(&out.buf, GotoDefinitionTarget::commit(repo, &commit));
 
-            out.push_code_actions(vec![CodeAction::new(&commit), CodeAction::abandon(&commit)]);
+            out.push_code_actions(vec![
+                CodeAction::new(repo.workspace_dir(), &commit),
+                CodeAction::edit(repo.workspace_dir(), &commit),
+                CodeAction::duplicate(repo.workspace_dir(), &commit),
+                CodeAction::abandon(repo.workspace_dir(), &commit),
+            ]);
             repo.write_log(&mut out.formatter(), &commit)?;
             out.pop_code_action();
             // writeln!(out)?;
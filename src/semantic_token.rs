@@ -37,6 +37,11 @@ pub fn get_or_default(name: &str) -> u32 {
 semantic_tokens!(
     // jjmagit labels
     "jjmagit",
+    "conflict_marker",
+    "conflict_base",
+    "conflict_side_1",
+    "conflict_side_2",
+    "conflict_side_3",
     // jj labels
     "access-denied",
     "added",